@@ -8,6 +8,10 @@ use relm4::{actions::AccelsPlus, RelmApp};
 
 relm4::new_action_group!(WindowActionGroup, "window");
 relm4::new_stateless_action!(SearchAction, WindowActionGroup, "search");
+relm4::new_stateless_action!(BackAction, WindowActionGroup, "back");
+relm4::new_stateless_action!(ForwardAction, WindowActionGroup, "forward");
+relm4::new_stateless_action!(NewTabAction, WindowActionGroup, "newtab");
+relm4::new_stateless_action!(CloseTabAction, WindowActionGroup, "closetab");
 
 fn main() {
     gtk::init().unwrap();
@@ -17,6 +21,10 @@ fn main() {
     let app = adw::Application::new(Some(APP_ID), gio::ApplicationFlags::empty());
     app.set_resource_base_path(Some("/dev/vlinkz/NixosConfEditor"));
     app.set_accelerators_for_action::<SearchAction>(&["<Control>f"]);
+    app.set_accelerators_for_action::<BackAction>(&["<Alt>Left"]);
+    app.set_accelerators_for_action::<ForwardAction>(&["<Alt>Right"]);
+    app.set_accelerators_for_action::<NewTabAction>(&["<Control>t"]);
+    app.set_accelerators_for_action::<CloseTabAction>(&["<Control>w"]);
     let app = RelmApp::with_app(app);
     app.run::<AppModel>(());
 }
@@ -0,0 +1,425 @@
+use super::optionpath;
+use super::options::{innerpath, isnested};
+use super::syntax;
+use rnix::{ast, SyntaxKind, SyntaxNode, SyntaxToken};
+use rowan::{ast::AstNode, NodeOrToken};
+use std::{collections::HashMap, ops::Range};
+
+/// How a binding is wrapped by the module system functions around it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    /// `mkDefault`, `mkForce`, `mkOverride 50`, ...
+    pub priority: Option<String>,
+    /// Conditions of the `mkIf`s the binding is nested in, outermost first
+    pub conditions: Vec<String>,
+}
+
+const PRIORITIES: [&str; 5] = [
+    "mkDefault",
+    "mkForce",
+    "mkOptionDefault",
+    "mkImageMediaOverride",
+    "mkVMOverride",
+];
+
+fn attrname(attr: &ast::Attr) -> Option<String> {
+    match attr {
+        ast::Attr::Ident(x) => x.ident_token().map(|t| t.text().to_string()),
+        ast::Attr::Str(x) => {
+            let mut out = String::new();
+            for part in x.normalized_parts() {
+                match part {
+                    ast::InterpolPart::Literal(s) => out.push_str(&s),
+                    ast::InterpolPart::Interpolation(_) => return None,
+                }
+            }
+            Some(out)
+        }
+        ast::Attr::Dynamic(_) => None,
+    }
+}
+
+/// Names of the attribute path of a binding, `None` if any of them is dynamic.
+pub fn attrnames(entry: &ast::AttrpathValue) -> Option<Vec<String>> {
+    entry
+        .attrpath()?
+        .attrs()
+        .map(|a| attrname(&a))
+        .collect::<Option<Vec<_>>>()
+}
+
+// Splits `lib.mkIf cond { ... }` into `mkIf` and its arguments
+pub fn applied(expr: &ast::Expr) -> Option<(String, Vec<ast::Expr>)> {
+    let mut args = vec![];
+    let mut e = expr.clone();
+    while let ast::Expr::Apply(x) = e {
+        args.push(x.argument()?);
+        e = x.lambda()?;
+    }
+    args.reverse();
+    let name = match e {
+        ast::Expr::Ident(x) => x.ident_token()?.text().to_string(),
+        ast::Expr::Select(x) => attrname(&x.attrpath()?.attrs().last()?)?,
+        _ => return None,
+    };
+    Some((name, args))
+}
+
+// Returns the priority wrapped around a value and the value inside of it
+fn priority(expr: &ast::Expr) -> Option<(String, ast::Expr)> {
+    let (name, mut args) = applied(expr)?;
+    if PRIORITIES.contains(&name.as_str()) && args.len() == 1 {
+        Some((name, args.pop()?))
+    } else if name == "mkOverride" && args.len() == 2 {
+        let value = args.pop()?;
+        Some((format!("mkOverride {}", args[0].syntax()), value))
+    } else {
+        None
+    }
+}
+
+/// The expression a configuration file evaluates to, without the `{ pkgs, ... }:` header
+pub fn configexpr(root: &SyntaxNode) -> Option<ast::Expr> {
+    match ast::Root::cast(root.clone())?.expr()? {
+        ast::Expr::Lambda(x) => x.body(),
+        x => Some(x),
+    }
+}
+
+fn collectexpr(
+    expr: ast::Expr,
+    prefix: &[String],
+    modifiers: &Modifiers,
+    out: &mut Vec<(String, String, Modifiers)>,
+) {
+    match expr {
+        ast::Expr::Paren(x) => {
+            if let Some(e) = x.expr() {
+                collectexpr(e, prefix, modifiers, out);
+            }
+        }
+        ast::Expr::LetIn(x) => {
+            if let Some(e) = x.body() {
+                collectexpr(e, prefix, modifiers, out);
+            }
+        }
+        ast::Expr::With(x) => {
+            if let Some(e) = x.body() {
+                collectexpr(e, prefix, modifiers, out);
+            }
+        }
+        // Nested configurations can be written as modules, like `{ pkgs, ... }: { ... }`
+        ast::Expr::Lambda(x) if isnested(innerpath(prefix)) => {
+            if let Some(e) = x.body() {
+                collectexpr(e, prefix, modifiers, out);
+            }
+        }
+        ast::Expr::AttrSet(x) if ast::HasEntry::entries(&x).next().is_some() => {
+            for entry in ast::HasEntry::attrpath_values(&x) {
+                if let (Some(attrs), Some(value)) = (attrnames(&entry), entry.value()) {
+                    let mut p = prefix.to_vec();
+                    p.extend(attrs);
+                    collectexpr(value, &p, modifiers, out);
+                }
+            }
+        }
+        e => {
+            if let Some((name, args)) = applied(&e) {
+                if name == "mkIf" && args.len() == 2 {
+                    let mut m = modifiers.clone();
+                    m.conditions.push(args[0].syntax().to_string());
+                    collectexpr(args[1].clone(), prefix, &m, out);
+                    return;
+                } else if name == "mkMerge" && args.len() == 1 {
+                    if let ast::Expr::List(list) = &args[0] {
+                        for item in list.items() {
+                            collectexpr(item, prefix, modifiers, out);
+                        }
+                        return;
+                    }
+                }
+            }
+            if let Some((name, value)) = priority(&e) {
+                let mut m = modifiers.clone();
+                m.priority = Some(name);
+                collectexpr(value, prefix, &m, out);
+            } else if !prefix.is_empty() {
+                out.push((
+                    optionpath::join(prefix),
+                    e.syntax().to_string(),
+                    modifiers.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Collects every option set in a configuration file with its value. Options
+/// inside `mkIf` and `mkMerge` are included, and priority wrappers such as
+/// `mkForce` are removed from the values and returned separately.
+pub fn collect(root: &SyntaxNode) -> HashMap<String, (String, Modifiers)> {
+    let mut out = vec![];
+    if let Some(expr) = configexpr(root) {
+        collectexpr(expr, &[], &Modifiers::default(), &mut out);
+    }
+    let mut map = HashMap::new();
+    for (k, v, m) in out {
+        // The first definition is the one edits are applied to
+        map.entry(k).or_insert((v, m));
+    }
+    map
+}
+
+// Finds the binding setting `path` and the expression holding its value,
+// which is inside of any priority wrapper. `parents` is the path `expr` is set at.
+fn findexpr(
+    expr: ast::Expr,
+    path: &[String],
+    parents: &[String],
+) -> Option<(ast::AttrpathValue, ast::Expr)> {
+    match expr {
+        ast::Expr::Paren(x) => findexpr(x.expr()?, path, parents),
+        ast::Expr::LetIn(x) => findexpr(x.body()?, path, parents),
+        ast::Expr::With(x) => findexpr(x.body()?, path, parents),
+        ast::Expr::Lambda(x) if isnested(innerpath(parents)) => findexpr(x.body()?, path, parents),
+        ast::Expr::AttrSet(x) => {
+            for entry in ast::HasEntry::attrpath_values(&x) {
+                let attrs = match attrnames(&entry) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                if attrs.len() > path.len() || !path[..attrs.len()].eq(&attrs) {
+                    continue;
+                }
+                let value = match entry.value() {
+                    Some(x) => x,
+                    None => continue,
+                };
+                if attrs.len() == path.len() {
+                    let mut value = value;
+                    while let Some((_, v)) = priority(&value) {
+                        value = v;
+                    }
+                    return Some((entry, value));
+                }
+                let rest = &path[attrs.len()..];
+                let mut p = parents.to_vec();
+                p.extend(attrs);
+                if let Some(x) = findexpr(value, rest, &p) {
+                    return Some(x);
+                }
+            }
+            None
+        }
+        e => {
+            let (name, args) = applied(&e)?;
+            if name == "mkIf" && args.len() == 2 {
+                findexpr(args[1].clone(), path, parents)
+            } else if name == "mkMerge" && args.len() == 1 {
+                match &args[0] {
+                    ast::Expr::List(list) => list.items().find_map(|x| findexpr(x, path, parents)),
+                    _ => None,
+                }
+            } else {
+                findexpr(priority(&e)?.1, path, parents)
+            }
+        }
+    }
+}
+
+/// Finds the value set for `path` inside of `expr`, without any priority wrapper.
+pub fn findvalue(expr: ast::Expr, path: &[String]) -> Option<ast::Expr> {
+    findexpr(expr, path, &[]).map(|(_, v)| v)
+}
+
+/// Finds the binding setting `path` in a parsed configuration file.
+pub fn findbinding(root: &SyntaxNode, path: &[String]) -> Option<ast::AttrpathValue> {
+    findexpr(configexpr(root)?, path, &[]).map(|(b, _)| b)
+}
+
+// Whether an element is the first thing on its line
+fn startsline(el: &NodeOrToken<SyntaxNode, SyntaxToken>) -> bool {
+    match el.prev_sibling_or_token() {
+        Some(NodeOrToken::Token(t)) if t.kind() == SyntaxKind::TOKEN_WHITESPACE => {
+            t.text().contains('\n')
+        }
+        Some(_) => false,
+        None => true,
+    }
+}
+
+/// Comment lines directly above a binding, without a blank line in between.
+fn leadingcomments(binding: &SyntaxNode) -> Vec<SyntaxToken> {
+    let mut out = vec![];
+    let mut prev = binding.prev_sibling_or_token();
+    while let Some(el) = prev {
+        match &el {
+            NodeOrToken::Token(t) if t.kind() == SyntaxKind::TOKEN_WHITESPACE => {
+                if t.text().matches('\n').count() > 1 {
+                    break;
+                }
+            }
+            NodeOrToken::Token(t) if t.kind() == SyntaxKind::TOKEN_COMMENT => {
+                // Comments after another binding on the same line belong to that binding
+                if !startsline(&el) {
+                    break;
+                }
+                out.push(t.clone());
+            }
+            _ => break,
+        }
+        prev = el.prev_sibling_or_token();
+    }
+    out.reverse();
+    out
+}
+
+// Whitespace between the start of the line and the binding
+fn indentation(f: &str, offset: usize) -> String {
+    let linestart = f[..offset].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    f[linestart..offset]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect()
+}
+
+fn commenttext(comment: &str) -> String {
+    if let Some(x) = comment.strip_prefix('#') {
+        x.strip_prefix(' ').unwrap_or(x).trim_end().to_string()
+    } else {
+        let x = comment.strip_prefix("/*").unwrap_or(comment);
+        let x = x.strip_suffix("*/").unwrap_or(x);
+        x.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Whether `val` has to be put in parentheses to be used as a function argument
+fn needsparens(val: &str) -> bool {
+    !matches!(
+        syntax::parse(val)
+            .ok()
+            .and_then(ast::Root::cast)
+            .and_then(|x| x.expr()),
+        Some(
+            ast::Expr::Literal(_)
+                | ast::Expr::Str(_)
+                | ast::Expr::Path(_)
+                | ast::Expr::Ident(_)
+                | ast::Expr::Select(_)
+                | ast::Expr::List(_)
+                | ast::Expr::AttrSet(_)
+                | ast::Expr::Paren(_)
+        )
+    )
+}
+
+/// Replaces the value of an existing binding without touching anything around it,
+/// so comments next to (and inside of) the surrounding attribute sets are kept.
+/// Values wrapped in `mkForce` and the like are replaced inside of the wrapper.
+/// Returns `None` if the binding cannot be found.
+pub fn replacevalue(f: &str, path: &[String], val: &str) -> Option<String> {
+    let root = syntax::parse(f).ok()?;
+    let (binding, value) = findexpr(configexpr(&root)?, path, &[])?;
+    let range = value.syntax().text_range();
+    let indent = indentation(f, usize::from(binding.syntax().text_range().start()));
+    let mut val = val
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            if i == 0 || l.is_empty() {
+                l.to_string()
+            } else {
+                format!("{}{}", indent, l)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if binding.value()?.syntax().text_range() != range && needsparens(&val) {
+        val = format!("({})", val);
+    }
+    let mut out = f.to_string();
+    out.replace_range(usize::from(range.start())..usize::from(range.end()), &val);
+    Some(out)
+}
+
+/// Removes the binding setting `path` and the rest of its line if it is empty.
+/// Returns `None` if the binding cannot be found.
+pub fn removebinding(f: &str, path: &[String]) -> Option<String> {
+    let root = syntax::parse(f).ok()?;
+    let binding = findbinding(&root, path)?;
+    let range = binding.syntax().text_range();
+    let mut start = usize::from(range.start());
+    let mut end = usize::from(range.end());
+    let linestart = f[..start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let lineend = f[end..].find('\n').map(|x| end + x + 1).unwrap_or(f.len());
+    if f[linestart..start].trim().is_empty() && f[end..lineend].trim().is_empty() {
+        start = linestart;
+        end = lineend;
+    }
+    let mut out = f.to_string();
+    out.replace_range(start..end, "");
+    Some(out)
+}
+
+/// Finds the nested configuration around `path` that is written as a module function,
+/// such as `containers.web.config = { pkgs, ... }: { ... };`. Returns the range of the
+/// function and the rest of `path` inside of it, `None` if there is no such module.
+pub fn nestedmodule(f: &str, path: &[String]) -> Option<(Range<usize>, Vec<String>)> {
+    let root = syntax::parse(f).ok()?;
+    let expr = configexpr(&root)?;
+    let mut end = 0;
+    while path.len() > end + 3 && isnested(&path[end..end + 3]) {
+        end += 3;
+        if let Some((_, ast::Expr::Lambda(x))) = findexpr(expr.clone(), &path[..end], &[]) {
+            let range = x.syntax().text_range();
+            return Some((
+                usize::from(range.start())..usize::from(range.end()),
+                path[end..].to_vec(),
+            ));
+        }
+    }
+    None
+}
+
+/// Reads the comment written directly above a binding.
+pub fn readnote(root: &SyntaxNode, path: &[String]) -> Option<String> {
+    let binding = findbinding(root, path)?;
+    let comments = leadingcomments(binding.syntax());
+    if comments.is_empty() {
+        None
+    } else {
+        Some(
+            comments
+                .iter()
+                .map(|x| commenttext(x.text()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+}
+
+/// Sets the comment directly above a binding to `note`, one `#` line per line of text.
+/// An empty note removes the comment. Returns `None` if the binding cannot be found.
+pub fn writenote(f: &str, path: &[String], note: &str) -> Option<String> {
+    let root = syntax::parse(f).ok()?;
+    let binding = findbinding(&root, path)?;
+    let bindingstart = usize::from(binding.syntax().text_range().start());
+    let start = match leadingcomments(binding.syntax()).first() {
+        Some(x) => usize::from(x.text_range().start()),
+        None => bindingstart,
+    };
+    let indent = indentation(f, start);
+    let comment = note
+        .lines()
+        .map(|l| l.trim_end())
+        .filter(|l| !l.is_empty())
+        .map(|l| format!("# {}\n{}", l, indent))
+        .collect::<String>();
+    let mut out = f.to_string();
+    out.replace_range(start..bindingstart, &comment);
+    Some(out)
+}
@@ -1,17 +1,9 @@
-use log::{debug, warn};
+use super::bindings;
+use super::optionpath::{join, split};
+use super::syntax::{self, ConfigError};
+use log::debug;
 use nix_editor;
-use std::{collections::HashMap, error::Error, fs, io, path::Path};
-
-pub fn parseconfig(path: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
-    let f = fs::read_to_string(Path::new(path))?;
-    match nix_editor::parse::get_collection(f) {
-        Ok(x) => Ok(x),
-        Err(_) => Err(Box::new(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Failed to parse config",
-        ))),
-    }
-}
+use std::collections::HashMap;
 
 pub fn opconfigured<T: std::fmt::Debug>(
     conf: &HashMap<String, T>,
@@ -21,7 +13,7 @@ pub fn opconfigured<T: std::fmt::Debug>(
     let mut p = pos.to_vec();
     p.push(attr);
     conf.keys().any(|k| {
-        let s = k.split('.').collect::<Vec<_>>();
+        let s = split(k);
         if s.len() < p.len() {
             false
         } else {
@@ -30,18 +22,10 @@ pub fn opconfigured<T: std::fmt::Debug>(
     })
 }
 
-pub fn opconfigured2(path: &str, pos: &[String], refpos: &[String], attr: String) -> bool {
-    let mut p = pos.to_vec();
-    p.push(attr.clone());
-    let mut r = refpos.to_vec();
-    r.push(attr);
-    readval(path, &p.join("."), &r.join(".")).is_ok()
-}
-
 pub fn getconfvals<T>(conf: &HashMap<String, T>, pos: &[String]) -> Vec<String> {
     let mut out = vec![];
     for attr in conf.keys() {
-        let k = attr.split('.').collect::<Vec<_>>();
+        let k = split(attr);
         if k.len() > pos.len() && k[0..pos.len()].eq(pos) {
             let x = k[pos.len()].to_string();
             if !out.contains(&x) {
@@ -52,36 +36,37 @@ pub fn getconfvals<T>(conf: &HashMap<String, T>, pos: &[String]) -> Vec<String>
     out
 }
 
-pub fn getarrvals(path: &str, pos: &[String]) -> Vec<String> {
-    let f = fs::read_to_string(Path::new(path)).unwrap();
-    let out = nix_editor::read::getarrvals(&f, &pos.join("."));
-    match out {
-        Ok(x) => x,
-        Err(_) => vec![],
-    }
-}
-
-pub fn editconfigpath(
-    path: &str,
-    editedopts: HashMap<String, String>,
-) -> Result<String, Box<dyn Error>> {
-    let f = fs::read_to_string(Path::new(path))?;
-    editconfig(f, editedopts)
-}
-
+/// Writes the edited options and notes into the configuration `f`.
+///
+/// An empty value removes the binding, while `null` is written like any other
+/// value so that it overrides a non-null default.
 pub fn editconfig(
     mut f: String,
     //path: &str,
     editedopts: HashMap<String, String>,
-) -> Result<String, Box<dyn Error>> {
+    editednotes: HashMap<String, String>,
+) -> Result<String, ConfigError> {
     debug!("editedopts: {:#?}", editedopts);
+    debug!("editednotes: {:#?}", editednotes);
+    syntax::parse(&f)?;
+    for (op, val) in editedopts.iter() {
+        if !val.is_empty() {
+            syntax::parse(val).map_err(|e| ConfigError::Value(op.to_string(), e))?;
+        }
+    }
     let mut k = editedopts.keys().collect::<Vec<_>>();
     k.sort();
 
     let mut starops: HashMap<String, HashMap<usize, String>> = HashMap::new();
     for (op, val) in editedopts.into_iter() {
-        if op.split('.').any(|x| x.parse::<usize>().is_ok()) {
-            let option = op.split('.').collect::<Vec<_>>();
+        let option = split(&op);
+        if let Some((range, inner)) = bindings::nestedmodule(&f, &option) {
+            // Nested configurations written as modules are edited like a file of their own
+            let mut h = HashMap::new();
+            h.insert(join(&inner), val);
+            let module = editconfig(f[range.clone()].to_string(), h, HashMap::new())?;
+            f.replace_range(range, &module);
+        } else if option.iter().any(|x| x.parse::<usize>().is_ok()) {
             let index = option
                 .iter()
                 .position(|x| x.parse::<usize>().is_ok())
@@ -90,14 +75,14 @@ pub fn editconfig(
             let v = &option[index + 1..];
             let i = option[index].parse::<usize>().unwrap();
 
-            let mut p = if let Some(y) = starops.get(&o.join(".")) {
+            let mut p = if let Some(y) = starops.get(&join(o)) {
                 y.to_owned()
             } else {
                 HashMap::new()
             };
             // fill up on first time
             if p.is_empty() {
-                let arr = match nix_editor::read::getarrvals(&f, &o.join(".")) {
+                let arr = match nix_editor::read::getarrvals(&f, &join(o)) {
                     Ok(x) => x,
                     Err(_) => vec![],
                 };
@@ -111,28 +96,32 @@ pub fn editconfig(
                 None => "{}".to_string(),
             };
             let mut h = HashMap::new();
-            h.insert(v.join("."), val);
-            p.insert(i, editconfig(arrval, h)?);
-            starops.insert(o.join("."), p);
+            h.insert(join(v), val);
+            p.insert(i, editconfig(arrval, h, HashMap::new())?);
+            starops.insert(join(o), p);
         } else if val.is_empty() {
-            f = match nix_editor::write::deref(&f, &op) {
-                Ok(x) => x,
-                Err(_) => {
-                    return Err(Box::new(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to deref {}", op),
-                    )))
-                }
+            f = match bindings::removebinding(&f, &option) {
+                Some(x) => x,
+                None => match nix_editor::write::deref(&f, &op) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        return Err(ConfigError::Edit(op, format!("could not remove ({})", e)))
+                    }
+                },
             };
         } else {
-            f = match nix_editor::write::write(&f, &op, &val) {
-                Ok(x) => x,
-                Err(_) => {
-                    return Err(Box::new(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to set value {} to {}", op, val),
-                    )))
-                }
+            // Edit existing bindings in place to keep the comments around them
+            f = match bindings::replacevalue(&f, &option, &val) {
+                Some(x) => x,
+                None => match nix_editor::write::write(&f, &op, &val) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        return Err(ConfigError::Edit(
+                            op,
+                            format!("could not set value to {} ({})", val, e),
+                        ))
+                    }
+                },
             };
         }
     }
@@ -158,83 +147,39 @@ pub fn editconfig(
         );
         f = match nix_editor::write::write(&f, &k, &valarr) {
             Ok(x) => x,
-            Err(_) => {
-                return Err(Box::new(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Failed to set value {} to {}", k, valarr),
-                )))
+            Err(e) => {
+                return Err(ConfigError::Edit(
+                    k,
+                    format!("could not set value to {} ({})", valarr, e),
+                ))
             }
         };
     }
+    // Notes are written last, so they can be attached to newly added bindings
+    for (op, note) in editednotes {
+        match bindings::writenote(&f, &split(&op), &note) {
+            Some(x) => f = x,
+            // Removing the note of an unset option leaves nothing to do
+            None if note.trim().is_empty() => {}
+            None => {
+                return Err(ConfigError::Edit(
+                    op,
+                    String::from("could not find the binding to attach the note to"),
+                ))
+            }
+        }
+    }
     Ok(f)
 }
 
-pub fn readval(path: &str, query: &str, refq: &str) -> Result<String, Box<dyn Error>> {
-    warn!("READVAL: {} {} {}", path, query, refq);
-    let f = fs::read_to_string(Path::new(path))?;
-    let out = if !refq.contains(&String::from("*")) {
-        nix_editor::read::readvalue(&f, query)
-    } else {
-        let p = refq.split('.').collect::<Vec<_>>();
-        let mut r: Vec<Vec<String>> = vec![vec![]];
-        let mut indexvec: Vec<usize> = vec![];
-        let mut j = 0;
-        for (i, attr) in p.iter().enumerate() {
-            if *attr == "*" {
-                r.push(vec![]);
-                if let Ok(x) = query.split('.').collect::<Vec<_>>()[i].parse::<usize>() {
-                    indexvec.push(x);
-                }
-                j += 1;
-            } else {
-                r[j].push(attr.to_string());
-            }
-        }
-        let mut f = fs::read_to_string(Path::new(path)).unwrap();
-        let mut i = 0;
-        for y in r {
-            if i < indexvec.len() {
-                f = match nix_editor::read::getarrvals(&f, &y.join(".")) {
-                    Ok(x) => {
-                        warn!("x: {:?}", x);
-                        let o = match x.get(indexvec[i]) {
-                            Some(x) => x.to_string(),
-                            None => {
-                                return Err(Box::new(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!("Index out of bounds {}", refq),
-                                )))
-                            }
-                        };
-                        i += 1;
-                        o
-                    }
-                    Err(_) => {
-                        return Err(Box::new(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Failed to read value from configuration",
-                        )))
-                    }
-                };
-            } else {
-                f = match nix_editor::read::readvalue(&f, &y.join(".")) {
-                    Ok(x) => x,
-                    Err(_) => {
-                        return Err(Box::new(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Failed to read value from configuration",
-                        )))
-                    }
-                };
-            }
-        }
-        Ok(f)
-    };
-    match out {
-        Ok(x) => Ok(x),
-        Err(_) => Err(Box::new(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to read value {}", query),
-        ))),
+/// Writes options as a NixOS module that can be imported into another configuration.
+pub fn exportmodule(opts: &[(String, String)]) -> String {
+    let mut out = String::from("{ config, pkgs, lib, ... }:\n\n{\n");
+    for (op, val) in opts {
+        // Keep multiline values aligned with their binding
+        let val = val.trim().replace('\n', "\n  ");
+        out.push_str(&format!("  {} = {};\n", op, val));
     }
+    out.push_str("}\n");
+    out
 }
@@ -0,0 +1,233 @@
+use super::bindings::{self, Modifiers};
+use super::config::{editconfig, opconfigured};
+use super::syntax::{self, ConfigError};
+use rnix::{ast, SyntaxNode};
+use rowan::{ast::AstNode, GreenNode};
+use std::{collections::HashMap, fs, path::Path};
+
+/// A configuration file, parsed once and kept in memory with the pending edits applied.
+///
+/// The syntax tree is kept as a green node, which unlike `SyntaxNode` can be
+/// sent between threads. Every read sees the pending edits, the file itself is
+/// only written once the document is serialised.
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+    path: String,
+    /// Text of the file as it was read
+    saved: String,
+    /// Options set in the file as it was read
+    savedvalues: HashMap<String, String>,
+    /// Text with the pending edits applied
+    text: String,
+    green: GreenNode,
+    /// Every option set with its value
+    values: HashMap<String, String>,
+    /// `mkIf` conditions and priorities of the options that have any
+    modifiers: HashMap<String, Modifiers>,
+    /// Pending values by option, an empty value removes the option
+    edits: HashMap<String, String>,
+    /// Pending notes by option
+    notes: HashMap<String, String>,
+}
+
+impl Default for ConfigDocument {
+    fn default() -> Self {
+        ConfigDocument {
+            path: String::new(),
+            saved: String::new(),
+            savedvalues: HashMap::new(),
+            text: String::new(),
+            green: rnix::Root::parse("").syntax().green().into_owned(),
+            values: HashMap::new(),
+            modifiers: HashMap::new(),
+            edits: HashMap::new(),
+            notes: HashMap::new(),
+        }
+    }
+}
+
+impl ConfigDocument {
+    pub fn open(path: &str) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(Path::new(path))?;
+        ConfigDocument::parse(path, text)
+    }
+
+    pub fn parse(path: &str, text: String) -> Result<Self, ConfigError> {
+        let mut document = ConfigDocument {
+            path: path.to_string(),
+            saved: text.to_string(),
+            text,
+            ..ConfigDocument::default()
+        };
+        document.reparse()?;
+        document.savedvalues = document.values.clone();
+        Ok(document)
+    }
+
+    // Parses `text` again after it changed
+    fn reparse(&mut self) -> Result<(), ConfigError> {
+        let root = syntax::parse(&self.text)?;
+        self.values.clear();
+        self.modifiers.clear();
+        for (op, (val, m)) in bindings::collect(&root) {
+            if m != Modifiers::default() {
+                self.modifiers.insert(op.to_string(), m);
+            }
+            self.values.insert(op, val);
+        }
+        self.green = root.green().into_owned();
+        Ok(())
+    }
+
+    // Writes the pending edits into the text as it was read, leaving the
+    // document unchanged if they cannot be applied.
+    fn apply(
+        &mut self,
+        edits: HashMap<String, String>,
+        notes: HashMap<String, String>,
+    ) -> Result<(), ConfigError> {
+        let text = editconfig(self.saved.to_string(), edits.clone(), notes.clone())?;
+        self.edits = edits;
+        self.notes = notes;
+        self.text = text;
+        self.reparse()
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Text with the pending edits applied.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Every option set with its value, including pending edits.
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    /// Pending values by option, an empty value removes the option.
+    pub fn edits(&self) -> &HashMap<String, String> {
+        &self.edits
+    }
+
+    /// Pending notes by option.
+    pub fn notes(&self) -> &HashMap<String, String> {
+        &self.notes
+    }
+
+    /// Whether there are edits that are not written to the file yet.
+    pub fn isedited(&self) -> bool {
+        !self.edits.is_empty() || !self.notes.is_empty()
+    }
+
+    /// Sets `opt` to `value`, an empty value removes it.
+    ///
+    /// Setting an option back to what the file has drops the edit.
+    pub fn edit(&mut self, opt: &str, value: &str) -> Result<(), ConfigError> {
+        let mut edits = self.edits.clone();
+        match self.savedvalues.get(opt) {
+            Some(x) if x == value => {
+                edits.remove(opt);
+            }
+            None if value.is_empty() => {
+                edits.remove(opt);
+            }
+            _ => {
+                edits.insert(opt.to_string(), value.to_string());
+            }
+        }
+        self.apply(edits, self.notes.clone())
+    }
+
+    pub fn editnote(&mut self, opt: &str, note: &str) -> Result<(), ConfigError> {
+        let mut notes = self.notes.clone();
+        notes.insert(opt.to_string(), note.to_string());
+        self.apply(self.edits.clone(), notes)
+    }
+
+    /// Drops the pending edits and notes of `opts`.
+    pub fn reset(&mut self, opts: &[String]) -> Result<(), ConfigError> {
+        let mut edits = self.edits.clone();
+        let mut notes = self.notes.clone();
+        edits.retain(|k, _| !opts.contains(k));
+        notes.retain(|k, _| !opts.contains(k));
+        self.apply(edits, notes)
+    }
+
+    /// Drops every pending edit and note.
+    pub fn resetall(&mut self) -> Result<(), ConfigError> {
+        self.apply(HashMap::new(), HashMap::new())
+    }
+
+    pub fn modifiers(&self) -> &HashMap<String, Modifiers> {
+        &self.modifiers
+    }
+
+    fn root(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    /// Finds the expression set for `pos`. Numeric segments index into lists,
+    /// so `*` options such as `fileSystems."/".options.0` can be read as well.
+    fn lookup(&self, pos: &[String]) -> Option<ast::Expr> {
+        let mut expr = bindings::configexpr(&self.root())?;
+        let mut start = 0;
+        for (i, attr) in pos.iter().enumerate() {
+            if let Ok(index) = attr.parse::<usize>() {
+                let value = if start == i {
+                    Some(expr.clone())
+                } else {
+                    bindings::findvalue(expr.clone(), &pos[start..i])
+                };
+                if let Some(ast::Expr::List(list)) = value {
+                    expr = list.items().nth(index)?;
+                    start = i + 1;
+                }
+            }
+        }
+        if start == pos.len() {
+            Some(expr)
+        } else {
+            bindings::findvalue(expr, &pos[start..])
+        }
+    }
+
+    /// Reads the value written for `pos`.
+    pub fn readval(&self, pos: &[String]) -> Option<String> {
+        self.lookup(pos).map(|x| x.syntax().to_string())
+    }
+
+    /// Reads the items of the list written for `pos`.
+    pub fn getarrvals(&self, pos: &[String]) -> Vec<String> {
+        match self.lookup(pos) {
+            Some(ast::Expr::List(list)) => list.items().map(|x| x.syntax().to_string()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether `attr` or anything below it is set under `pos`.
+    pub fn configured(&self, pos: &[String], attr: String) -> bool {
+        let mut p = pos.to_vec();
+        p.push(attr.clone());
+        opconfigured(&self.values, pos, attr) || self.lookup(&p).is_some()
+    }
+
+    pub fn readnote(&self, pos: &[String]) -> Option<String> {
+        bindings::readnote(&self.root(), pos)
+    }
+
+    /// Text of the file with the pending edits written into it.
+    ///
+    /// The file is read again first, so changes made to it since it was opened
+    /// are kept instead of being overwritten.
+    pub fn serialize(&self) -> Result<String, ConfigError> {
+        let current = fs::read_to_string(Path::new(&self.path))?;
+        if current == self.saved {
+            Ok(self.text.to_string())
+        } else {
+            editconfig(current, self.edits.clone(), self.notes.clone())
+        }
+    }
+}
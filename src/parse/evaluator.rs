@@ -0,0 +1,334 @@
+use super::tempdir::TempDir;
+use log::{debug, info, warn};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// Longest a single request may take before the session is restarted
+const TIMEOUT: Duration = Duration::from_secs(120);
+// Loading the module system takes a lot longer than a request
+const STARTTIMEOUT: Duration = Duration::from_secs(600);
+// Longest to wait for errors after the output of a request
+const STDERRTIMEOUT: Duration = Duration::from_secs(5);
+// A session that has to be started this often within RESTARTWINDOW is given up on
+const MAXSTARTS: usize = 4;
+const RESTARTWINDOW: Duration = Duration::from_secs(600);
+
+static SESSIONS: Mutex<Vec<Arc<Slot>>> = Mutex::new(Vec::new());
+static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum EvalError {
+    /// The expression did not evaluate, with the error reported by nix
+    Eval(String),
+    /// No evaluator is running, a one-off `nix-instantiate` should be used instead
+    Unavailable(String),
+}
+
+/// A session shared between threads, locked only while it is used.
+///
+/// Loading the module system can take minutes, so sessions are only started in
+/// the background and requests made meanwhile are told the evaluator is unavailable.
+struct Slot {
+    context: String,
+    /// The session has a process that finished loading
+    running: AtomicBool,
+    /// A thread is starting the session
+    starting: AtomicBool,
+    session: Mutex<Session>,
+}
+
+/// A `nix repl` with `sys` bound to an evaluated expression, usually a NixOS system.
+struct Session {
+    context: String,
+    process: Option<Process>,
+    starts: Vec<Instant>,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: mpsc::Receiver<String>,
+    stderr: Arc<Mutex<String>>,
+}
+
+/// Evaluates `body` with `sys` bound to `context` and returns the JSON it evaluates to.
+///
+/// Sessions are kept alive between calls, so the module system of `context` is
+/// only loaded once.
+pub fn eval(context: &str, body: &str) -> Result<String, EvalError> {
+    let slot = slot(context);
+    if !slot.running.load(Ordering::SeqCst) {
+        start(slot);
+        return Err(EvalError::Unavailable(String::from(
+            "Evaluator is still starting",
+        )));
+    }
+    let mut session = slot.session.lock().unwrap_or_else(|e| e.into_inner());
+    let out = session.request(body);
+    slot.running
+        .store(session.process.is_some(), Ordering::SeqCst);
+    out
+}
+
+/// Starts a session in the background, so the first request does not have to wait for it.
+pub fn warmup(context: &str) {
+    start(slot(context));
+}
+
+// Finds the session for `context`, adding it if there is none yet
+fn slot(context: &str) -> Arc<Slot> {
+    let mut sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    match sessions.iter().find(|x| x.context == context) {
+        Some(x) => x.clone(),
+        None => {
+            let slot = Arc::new(Slot {
+                context: context.to_string(),
+                running: AtomicBool::new(false),
+                starting: AtomicBool::new(false),
+                session: Mutex::new(Session {
+                    context: context.to_string(),
+                    process: None,
+                    starts: vec![],
+                }),
+            });
+            sessions.push(slot.clone());
+            slot
+        }
+    }
+}
+
+// Starts the session of `slot` on its own thread, unless another thread already is
+fn start(slot: Arc<Slot>) {
+    if slot.starting.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || {
+        let mut session = slot.session.lock().unwrap_or_else(|e| e.into_inner());
+        if session.process.is_none() {
+            if let Err(e) = session.start() {
+                warn!("Could not start evaluator: {:?}", e);
+            }
+        }
+        slot.running
+            .store(session.process.is_some(), Ordering::SeqCst);
+        slot.starting.store(false, Ordering::SeqCst);
+    });
+}
+
+impl Session {
+    fn request(&mut self, body: &str) -> Result<String, EvalError> {
+        if self.process.is_none() {
+            return Err(EvalError::Unavailable(String::from(
+                "Evaluator not running",
+            )));
+        }
+        let n = REQUESTS.fetch_add(1, Ordering::Relaxed);
+        // The repl reads one line at a time, so multiline values go through a file
+        let dir = TempDir::new("nce-eval").map_err(|e| EvalError::Unavailable(e.to_string()))?;
+        let file = dir.path().join("expr.nix");
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file)
+            .and_then(|mut f| writeln!(f, "sys: {}", body))
+            .map_err(|e| EvalError::Unavailable(e.to_string()))?;
+        let (lines, stderr) = self.send(
+            &format!("builtins.toJSON (import {} sys)", file.to_string_lossy()),
+            n,
+            TIMEOUT,
+        )?;
+        match lines.iter().rev().find_map(|x| unquote(x)) {
+            Some(json) => Ok(json),
+            None => Err(EvalError::Eval(stderr.trim().to_string())),
+        }
+    }
+
+    fn start(&mut self) -> Result<(), EvalError> {
+        self.starts.retain(|x| x.elapsed() < RESTARTWINDOW);
+        if self.starts.len() >= MAXSTARTS {
+            return Err(EvalError::Unavailable(String::from(
+                "Evaluator restarted too often",
+            )));
+        }
+        self.starts.push(Instant::now());
+        info!("Starting evaluator for {}", self.context);
+        let mut child = Command::new("nix")
+            .arg("--extra-experimental-features")
+            .arg("nix-command flakes")
+            .arg("repl")
+            .arg("--impure")
+            .env("NO_COLOR", "1")
+            .env("TERM", "dumb")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| EvalError::Unavailable(e.to_string()))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let mut stderrpipe = child.stderr.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let errbuf = stderr.clone();
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            while let Ok(n) = stderrpipe.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                errbuf
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+        });
+        self.process = Some(Process {
+            child,
+            stdin,
+            stdout: rx,
+            stderr,
+        });
+        let n = REQUESTS.fetch_add(1, Ordering::Relaxed);
+        self.send(&format!("sys = {}", self.context), n, TIMEOUT)?;
+        // Load the module system now instead of on the first request
+        let n = REQUESTS.fetch_add(1, Ordering::Relaxed);
+        let (lines, stderr) = self.send(
+            "builtins.seq (builtins.attrNames (sys.options or { })) null",
+            n,
+            STARTTIMEOUT,
+        )?;
+        if !lines.iter().any(|x| x == "null") {
+            self.stop();
+            return Err(EvalError::Unavailable(stderr.trim().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sends a line to the repl and collects the output up to a marker printed after it.
+    ///
+    /// The marker is traced as well, so errors written to stderr before it are
+    /// known to be complete once the trace is read.
+    fn send(
+        &mut self,
+        line: &str,
+        n: usize,
+        timeout: Duration,
+    ) -> Result<(Vec<String>, String), EvalError> {
+        let process = match &mut self.process {
+            Some(x) => x,
+            None => {
+                return Err(EvalError::Unavailable(String::from(
+                    "Evaluator not running",
+                )))
+            }
+        };
+        process
+            .stderr
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        let marker = format!("nce-done-{}", n);
+        debug!("Sending {} to evaluator", line);
+        if let Err(e) = writeln!(
+            process.stdin,
+            "{}\nbuiltins.trace \"{}\" \"{}\"",
+            line, marker, marker
+        ) {
+            self.stop();
+            return Err(EvalError::Unavailable(e.to_string()));
+        }
+        let _ = process.stdin.flush();
+        let deadline = Instant::now() + timeout;
+        let mut lines = vec![];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match process.stdout.recv_timeout(remaining) {
+                Ok(x) if x.contains(&marker) => break,
+                Ok(x) => lines.push(x.trim().to_string()),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    warn!("Evaluator timed out, restarting it");
+                    self.stop();
+                    return Err(EvalError::Eval(format!(
+                        "Evaluation did not finish within {} seconds",
+                        timeout.as_secs()
+                    )));
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    let stderr = process
+                        .stderr
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .to_string();
+                    self.stop();
+                    return Err(EvalError::Unavailable(stderr));
+                }
+            }
+        }
+        // stderr is read on another thread, so it may still be behind stdout
+        let deadline = Instant::now() + STDERRTIMEOUT;
+        loop {
+            let stderr = process
+                .stderr
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .to_string();
+            if let Some(i) = stderr.find(&marker) {
+                let linestart = stderr[..i].rfind('\n').map(|x| x + 1).unwrap_or(0);
+                return Ok((lines, stderr[..linestart].to_string()));
+            }
+            if Instant::now() >= deadline {
+                warn!("Evaluator did not trace {}", marker);
+                return Ok((lines, stderr));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+}
+
+/// Reads a string printed by the repl, such as `"{\"a\":1}"`.
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                x => out.push(x),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
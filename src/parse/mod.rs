@@ -1,3 +1,14 @@
+pub mod bindings;
 pub mod config;
+pub mod document;
+pub mod evaluator;
+pub mod nixpkgs;
+pub mod nixstring;
+pub mod optiondb;
+pub mod optionpath;
 pub mod options;
 pub mod preferences;
+pub mod search;
+pub mod syntax;
+pub mod tempdir;
+pub mod validate;
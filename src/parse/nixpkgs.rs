@@ -0,0 +1,200 @@
+use super::bindings;
+use super::nixstring;
+use super::optionpath;
+use super::syntax;
+use log::{info, warn};
+use rnix::ast;
+use rowan::ast::AstNode;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+pub const CHANNELPATH: &str = "/nix/var/nix/profiles/per-user/root/channels/nixos";
+
+// Store paths of locked nixpkgs by the expression they were found with and the `flake.lock` at the time
+static LOCKED: Mutex<Vec<(String, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Finds the nixpkgs checkout the system is built from: the nixpkgs used by
+/// the configuration `host` of the flake in the given directory, the root
+/// channel, or the nixpkgs of the flake registry.
+pub fn nixpkgspath(flake: Option<(&Path, &str)>) -> Option<PathBuf> {
+    if let Some((dir, host)) = flake {
+        match lockednixpkgs(dir, host) {
+            Some(path) => return Some(path),
+            None => warn!(
+                "Could not find the nixpkgs of {} in {}, using the channel or the registry instead",
+                host,
+                dir.to_string_lossy()
+            ),
+        }
+    }
+    if Path::new(CHANNELPATH).exists() {
+        return Some(PathBuf::from(CHANNELPATH));
+    }
+    let output = Command::new("nix")
+        .arg("eval")
+        .arg("nixpkgs#path")
+        .output()
+        .ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !path.is_empty() {
+        Some(PathBuf::from(path))
+    } else {
+        None
+    }
+}
+
+/// Store path of the nixpkgs the configuration `host` of the flake in `flakedir` is built from.
+///
+/// The configuration is asked for its `pkgs.path`, so it does not matter what
+/// the input is called. If it cannot be evaluated, the input named `nixpkgs`
+/// in `flake.lock` is used instead. Both are looked up with `--offline` first,
+/// so no network access is needed when they are in the store already, which is
+/// the case for any flake that has been built before.
+pub fn lockednixpkgs(flakedir: &Path, host: &str) -> Option<PathBuf> {
+    let lock = fs::read_to_string(flakedir.join("flake.lock")).ok()?;
+    let mut exprs = vec![format!(
+        "toString (builtins.getFlake {}).nixosConfigurations.{}.pkgs.path",
+        nixstring::encode(&flakedir.to_string_lossy()),
+        nixstring::encode(host)
+    )];
+    exprs.extend(fetchtree(&lock).map(|x| format!("({}).outPath", x)));
+    for (i, expr) in exprs.into_iter().enumerate() {
+        let key = format!("{}\n{}", expr, lock);
+        {
+            let cache = LOCKED.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((_, path)) = cache.iter().find(|(x, path)| x == &key && path.exists()) {
+                return Some(path.to_path_buf());
+            }
+        }
+        // Not locked while fetching, which may need the network
+        if let Some(path) = fetch(&expr, true).or_else(|| fetch(&expr, false)) {
+            if i > 0 {
+                warn!(
+                    "Could not evaluate the nixpkgs of {}, using the nixpkgs input of the flake instead",
+                    host
+                );
+            }
+            info!("Locked nixpkgs: {}", path.to_string_lossy());
+            let mut cache = LOCKED.lock().unwrap_or_else(|e| e.into_inner());
+            if !cache.iter().any(|(x, _)| x == &key) {
+                cache.push((key, path.to_path_buf()));
+            }
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// `builtins.fetchTree` call for the nixpkgs input of the root of a `flake.lock`.
+fn fetchtree(lock: &str) -> Option<String> {
+    let lock = serde_json::from_str::<Value>(lock).ok()?;
+    let nodes = lock.get("nodes")?;
+    let root = lock.get("root")?.as_str()?;
+    let node = match nodes.get(root)?.get("inputs")?.get("nixpkgs")? {
+        Value::String(x) => x.to_string(),
+        // Follows another input, given as a path of input names from the root
+        Value::Array(path) => {
+            let mut node = root.to_string();
+            for input in path {
+                node = nodes
+                    .get(&node)?
+                    .get("inputs")?
+                    .get(input.as_str()?)?
+                    .as_str()?
+                    .to_string();
+            }
+            node
+        }
+        _ => return None,
+    };
+    let attrs = nodes
+        .get(&node)?
+        .get("locked")?
+        .as_object()?
+        .iter()
+        .map(|(k, v)| {
+            let v = match v {
+                Value::String(s) => nixstring::encode(s),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return None,
+            };
+            Some(format!("{} = {};", optionpath::quote(k), v))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("builtins.fetchTree {{ {} }}", attrs.join(" ")))
+}
+
+// Evaluates `expr` to a store path, fetching what it needs unless `offline`
+fn fetch(expr: &str, offline: bool) -> Option<PathBuf> {
+    let mut cmd = Command::new("nix");
+    cmd.arg("--extra-experimental-features")
+        .arg("nix-command flakes")
+        .arg("eval")
+        .arg("--raw")
+        .arg("--impure");
+    if offline {
+        cmd.arg("--offline");
+    }
+    let output = cmd.arg("--expr").arg(expr).output().ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !path.is_empty() {
+        Some(PathBuf::from(path))
+    } else {
+        None
+    }
+}
+
+/// Resolves a declaration from options.json, which is relative to the nixpkgs root.
+pub fn resolve(nixpkgs: Option<&Path>, declaration: &str) -> Option<PathBuf> {
+    let path = Path::new(declaration);
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        nixpkgs.map(|x| x.join(path))
+    }
+}
+
+/// Finds the line of the module in `text` that declares `opt`, starting at 1.
+pub fn declarationline(text: &str, opt: &[String]) -> Option<usize> {
+    let root = rnix::Root::parse(text).syntax();
+    let mut best: Option<(usize, usize)> = None;
+    for entry in root.descendants().filter_map(ast::AttrpathValue::cast) {
+        match entry.value().and_then(|x| bindings::applied(&x)) {
+            Some((f, _)) if f.starts_with("mk") && f.contains("Option") => {}
+            _ => continue,
+        }
+        // Full path of the binding including the attribute sets it is nested in
+        let mut names = vec![];
+        for parent in entry
+            .syntax()
+            .ancestors()
+            .filter_map(ast::AttrpathValue::cast)
+        {
+            match bindings::attrnames(&parent) {
+                Some(x) => {
+                    names.splice(0..0, x);
+                }
+                None => break,
+            }
+        }
+        if names.first().map(|x| x == "options").unwrap_or(false) {
+            names.remove(0);
+        }
+        let matched = names
+            .iter()
+            .rev()
+            .zip(opt.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if matched > 0 && best.map(|(m, _)| matched > m).unwrap_or(true) {
+            best = Some((matched, usize::from(entry.syntax().text_range().start())));
+        }
+    }
+    best.map(|(_, offset)| syntax::linecol(text, offset).0)
+}
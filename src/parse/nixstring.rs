@@ -0,0 +1,267 @@
+// Indentation of the lines of indented strings written by `encodeindented`
+const INDENT: &str = "  ";
+
+/// Writes `s` as a double quoted Nix string.
+pub fn encode(s: &str) -> String {
+    let mut out = String::from("\"");
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes `s` as an indented Nix string, one line of text per line of code.
+///
+/// Text that would not read back the same, such as text where every line
+/// starts with a space, is written as a double quoted string instead.
+pub fn encodeindented(s: &str) -> String {
+    let lines = s
+        .split('\n')
+        .map(|line| {
+            let mut out = String::new();
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        out.push_str("'''");
+                    }
+                    '$' if chars.peek() == Some(&'{') => out.push_str("''$"),
+                    '\r' => out.push_str("''\\r"),
+                    _ => out.push(c),
+                }
+            }
+            if out.is_empty() {
+                out
+            } else {
+                format!("{}{}", INDENT, out)
+            }
+        })
+        .collect::<Vec<_>>();
+    let out = format!("''\n{}''", lines.join("\n"));
+    if decode(&out).as_deref() == Some(s) {
+        out
+    } else {
+        encode(s)
+    }
+}
+
+/// Reads a string literal, `None` if `s` is not a single string without interpolations.
+pub fn decode(s: &str) -> Option<String> {
+    let s = s.trim();
+    if let Some(body) = s.strip_prefix("''") {
+        decodeindented(body)
+    } else if let Some(body) = s.strip_prefix('"') {
+        decodequoted(body)
+    } else {
+        None
+    }
+}
+
+/// Whether `s` is an indented string.
+pub fn isindented(s: &str) -> bool {
+    s.trim().starts_with("''")
+}
+
+/// Whether `s` is best edited as several lines of text.
+pub fn ismultiline(s: &str) -> bool {
+    isindented(s) || decode(s).map(|x| x.contains('\n')).unwrap_or(false)
+}
+
+fn decodequoted(body: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                return if chars.next().is_none() {
+                    Some(out)
+                } else {
+                    None
+                }
+            }
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                x => out.push(x),
+            },
+            '$' => match chars.peek() {
+                Some('{') => return None,
+                // `$${` is not an interpolation
+                Some('$') => {
+                    chars.next();
+                    out.push_str("$$");
+                }
+                _ => out.push('$'),
+            },
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+fn decodeindented(body: &str) -> Option<String> {
+    // Find the closing quotes first, escapes are resolved after removing the
+    // indentation as in Nix
+    let mut rest = body;
+    let mut len = 0;
+    loop {
+        if let Some(after) = rest.strip_prefix("''") {
+            let escaped = match after.chars().next() {
+                Some('\'') | Some('$') => 3,
+                Some('\\') => 2 + after.chars().nth(1)?.len_utf8() + 1,
+                _ => {
+                    if !after.is_empty() {
+                        return None;
+                    }
+                    break;
+                }
+            };
+            len += escaped;
+            rest = &rest[escaped..];
+        } else if rest.starts_with("${") {
+            return None;
+        } else if rest.starts_with("$$") {
+            len += 2;
+            rest = &rest[2..];
+        } else {
+            let c = rest.chars().next()?;
+            len += c.len_utf8();
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    let raw = &body[..len];
+
+    let lines = raw.split('\n').collect::<Vec<_>>();
+    let blank = |line: &str| line.chars().all(|c| c == ' ');
+    let indent = lines
+        .iter()
+        .filter(|line| !blank(line))
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+    let mut stripped = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>();
+    if stripped.len() > 1 && blank(lines[0]) {
+        stripped.remove(0);
+    }
+    let text = stripped.join("\n");
+
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\'' && chars.as_str().starts_with('\'') {
+            chars.next();
+            match chars.next() {
+                Some('\'') => out.push_str("''"),
+                Some('$') => out.push('$'),
+                Some('\\') => match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some(x) => out.push(x),
+                    None => return None,
+                },
+                _ => return None,
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [&str; 16] = [
+        "",
+        "plain",
+        "with \"quotes\"",
+        "back\\slash",
+        "trailing\\",
+        "${interpolation}",
+        "$${not interpolation}",
+        "$",
+        "''$",
+        "'''",
+        "''",
+        "tab\there",
+        "line one\nline two",
+        "crlf\r\nline",
+        "\nleading and trailing\n",
+        "ünïcödé ✓",
+    ];
+
+    #[test]
+    fn quoted_roundtrip() {
+        for s in SAMPLES {
+            assert_eq!(decode(&encode(s)).as_deref(), Some(s), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn indented_roundtrip() {
+        let mut samples = SAMPLES.to_vec();
+        samples.extend([
+            "  every line\n  indented",
+            "mixed\n  indentation\n    deeper\nnone",
+            " one space",
+            "blank\n\nlines\n   \nbetween",
+            "ends with quote'",
+            "'starts with quote",
+        ]);
+        for s in samples {
+            assert_eq!(decode(&encodeindented(s)).as_deref(), Some(s), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn indented_when_possible() {
+        assert_eq!(encodeindented("a\nb"), "''\n  a\n  b''");
+        assert_eq!(encodeindented("x ${y}"), "''\n  x ''${y}''");
+        assert_eq!(encodeindented("a''b"), "''\n  a'''b''");
+    }
+
+    #[test]
+    fn indented_fallback() {
+        // The common indentation would be stripped when read back
+        assert_eq!(encodeindented(" a\n b"), encode(" a\n b"));
+        assert_eq!(encodeindented("  only"), encode("  only"));
+    }
+
+    #[test]
+    fn decode_strings() {
+        assert_eq!(decode("\"a\\nb\"").as_deref(), Some("a\nb"));
+        assert_eq!(
+            decode("''\n    a\n      b\n  ''").as_deref(),
+            Some("a\n  b\n")
+        );
+        assert_eq!(decode("''a''\\nb''").as_deref(), Some("a\nb"));
+        assert_eq!(decode("''a'''b''").as_deref(), Some("a''b"));
+    }
+
+    #[test]
+    fn decode_rejects() {
+        assert_eq!(decode("\"${x}\""), None);
+        assert_eq!(decode("''${x}''"), None);
+        assert_eq!(decode("\"a\" + \"b\""), None);
+        assert_eq!(decode("''a'' + ''b''"), None);
+        assert_eq!(decode("\"unterminated"), None);
+        assert_eq!(decode("x"), None);
+    }
+}
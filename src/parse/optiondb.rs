@@ -0,0 +1,213 @@
+use super::optionpath;
+use super::options::{attrloc, buildtree, innerpath, readdata, refpath, AttrTree, OptionData};
+use super::search::{Query, SearchEntry, SearchIndex, SearchResult};
+use log::*;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Every declared option, loaded once and shared between components behind an `Arc`.
+///
+/// Holds the option data by name, the attribute tree for path lookups and the
+/// search index, so none of them need to be copied around when navigating.
+#[derive(Debug, Default)]
+pub struct OptionDb {
+    data: HashMap<String, OptionData>,
+    tree: AttrTree,
+    index: SearchIndex,
+    /// Label of the additional options file each option outside NixOS was read from
+    sources: HashMap<String, String>,
+    /// Label of the additional options file of namespaces holding only options from that file
+    namespaces: HashMap<String, String>,
+}
+
+impl OptionDb {
+    /// Loads the NixOS options in `file` together with the options of private modules.
+    ///
+    /// Each entry of `extra` is an options file or a directory of them. Extra files
+    /// that fail to load are skipped, and options NixOS already declares are kept.
+    pub fn load(file: &str, extra: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut data = readdata(Path::new(file))?;
+        let mut sources = HashMap::new();
+        for path in extra.iter().flat_map(|x| optionfiles(Path::new(x))) {
+            let extradata = match readdata(&path) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("Failed to load options from {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let label = sourcelabel(&path);
+            let mut duplicates = 0;
+            for (name, op) in extradata {
+                if data.contains_key(&name) {
+                    duplicates += 1;
+                    continue;
+                }
+                sources.insert(name.to_string(), label.to_string());
+                data.insert(name, op);
+            }
+            if duplicates > 0 {
+                warn!(
+                    "Skipped {} options of {} that are already declared",
+                    duplicates,
+                    path.display()
+                );
+            }
+        }
+        let tree = buildtree(data.keys().map(|x| x.as_str()).collect())?;
+        let namespaces = namespaces(&data, &sources);
+        Ok(OptionDb {
+            sources,
+            namespaces,
+            ..OptionDb::new(data, tree)
+        })
+    }
+
+    pub fn new(data: HashMap<String, OptionData>, tree: AttrTree) -> Self {
+        let entries = data
+            .iter()
+            .map(|(name, data)| SearchEntry {
+                name: name.to_string(),
+                description: description(data),
+                op_type: data.op_type.to_string(),
+                read_only: data.read_only,
+                declarations: data.declarations.iter().map(|x| x.to_string()).collect(),
+                default: data.default.is_some(),
+            })
+            .collect::<Vec<_>>();
+        OptionDb {
+            data,
+            tree,
+            index: SearchIndex::new(entries),
+            sources: HashMap::new(),
+            namespaces: HashMap::new(),
+        }
+    }
+
+    /// Option data of `name`. Options inside nested configurations such as
+    /// `containers.<name>.config` share the data of the top level options.
+    pub fn get(&self, name: &str) -> Option<&OptionData> {
+        self.data.get(&declared(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.data.contains_key(&declared(name))
+    }
+
+    /// Label of the additional options file `name` was declared in, `None` for NixOS options.
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.sources.get(&declared(name)).map(|x| x.as_str())
+    }
+
+    /// Label of the additional options file every option under the namespace `name`
+    /// was declared in, `None` if any of them come from NixOS or another file.
+    pub fn namespacesource(&self, name: &str) -> Option<&str> {
+        self.namespaces.get(&declared(name)).map(|x| x.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.data.keys()
+    }
+
+    /// Type description of the option declared as `name`.
+    pub fn op_type(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|x| x.op_type.as_str())
+    }
+
+    pub fn tree(&self) -> &AttrTree {
+        &self.tree
+    }
+
+    pub fn attrloc(&self, pos: Vec<String>) -> Option<&AttrTree> {
+        attrloc(&self.tree, pos)
+    }
+
+    /// See [`refpath`].
+    pub fn refpath(&self, pos: &[String]) -> Option<Vec<String>> {
+        refpath(&self.tree, pos)
+    }
+
+    /// Finds options matching `query`. `configured` tells whether an option is
+    /// set in the configuration or has pending edits.
+    pub fn search(&self, query: &Query, configured: impl Fn(&str) -> bool) -> Vec<SearchResult> {
+        self.index.search(query, configured)
+    }
+}
+
+// Sources of the namespaces whose options all come from the same additional file
+fn namespaces(
+    data: &HashMap<String, OptionData>,
+    sources: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    // `None` once a namespace holds options from more than one place
+    let mut found: HashMap<String, Option<&str>> = HashMap::new();
+    for name in data.keys() {
+        let source = sources.get(name).map(|x| x.as_str());
+        let path = optionpath::split(name);
+        for i in 1..path.len() {
+            found
+                .entry(optionpath::join(&path[..i]))
+                .and_modify(|x| {
+                    if *x != source {
+                        *x = None
+                    }
+                })
+                .or_insert(source);
+        }
+    }
+    found
+        .into_iter()
+        .filter_map(|(name, source)| Some((name, source?.to_string())))
+        .collect()
+}
+
+// Name of the top level option `name` is declared as
+fn declared(name: &str) -> String {
+    optionpath::join(innerpath(&optionpath::split(name)))
+}
+
+// Descriptions are either plain strings or `{ _type = "mdDoc"; text = ...; }`
+fn description(data: &OptionData) -> String {
+    data.description
+        .as_object()
+        .and_then(|x| x.get("text"))
+        .unwrap_or(&data.description)
+        .as_string()
+        .map(|x| x.to_string())
+        .unwrap_or_default()
+}
+
+// A directory stands for the json files directly inside it
+fn optionfiles(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut out = match fs::read_dir(path) {
+        Ok(x) => x
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| x.is_file() && x.extension().map(|e| e == "json").unwrap_or(false))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            vec![]
+        }
+    };
+    out.sort();
+    out
+}
+
+// Options files are usually all called `options.json`, so those are named after their directory
+fn sourcelabel(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    if stem == "options" {
+        if let Some(dir) = path.parent().and_then(|x| x.file_name()) {
+            return dir.to_string_lossy().to_string();
+        }
+    }
+    stem.to_string()
+}
@@ -0,0 +1,99 @@
+// Option names in their Nix form, like `services.nginx.virtualHosts."example.com".root`,
+// so a name containing a dot or other special characters stays a single segment.
+
+use super::nixstring;
+
+/// Segments standing for any name in option declarations, these are never quoted.
+const PLACEHOLDERS: [&str; 3] = ["<name>", "<name?>", "*"];
+const KEYWORDS: [&str; 9] = [
+    "assert", "else", "if", "in", "inherit", "let", "rec", "then", "with",
+];
+
+/// Writes a segment as a Nix attribute name, quoting it if it is not a plain identifier.
+pub fn quote(segment: &str) -> String {
+    let mut chars = segment.chars();
+    let ident = match chars.next() {
+        Some(c) => {
+            (c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|x| x.is_ascii_alphanumeric() || "_'-".contains(x))
+        }
+        None => false,
+    };
+    if PLACEHOLDERS.contains(&segment) || (ident && !KEYWORDS.contains(&segment)) {
+        segment.to_string()
+    } else {
+        nixstring::encode(segment)
+    }
+}
+
+/// Splits an option name into its unquoted segments.
+pub fn split(s: &str) -> Vec<String> {
+    let mut segments = vec![];
+    if s.is_empty() {
+        return segments;
+    }
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => match chars.next() {
+                Some('n') => current.push('\n'),
+                Some('t') => current.push('\t'),
+                Some('r') => current.push('\r'),
+                Some(x) => current.push(x),
+                None => current.push('\\'),
+            },
+            '.' if !quoted => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+}
+
+/// Joins segments into an option name, quoting them where needed.
+pub fn join(segments: &[String]) -> String {
+    segments
+        .iter()
+        .map(|x| quote(x))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Byte ranges of the segments of an option name as written, leaving out the
+/// quotes around quoted segments.
+pub fn segmentranges(s: &str) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    if s.is_empty() {
+        return ranges;
+    }
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '.' if !quoted => {
+                ranges.push(unquoted(s, start, i));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ranges.push(unquoted(s, start, s.len()));
+    ranges
+}
+
+// Range of `s[start..end]` without the quotes around it
+fn unquoted(s: &str, start: usize, end: usize) -> (usize, usize) {
+    let segment = &s[start..end];
+    if segment.len() >= 2 && segment.starts_with('"') && segment.ends_with('"') {
+        (start + 1, end - 1)
+    } else {
+        (start, end)
+    }
+}
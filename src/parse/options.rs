@@ -1,7 +1,8 @@
+use super::optionpath;
 use ijson::{IString, IValue};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::{self, cmp::Ordering, collections::HashMap, error::Error, fs};
+use std::{self, cmp::Ordering, collections::HashMap, error::Error, fs, path::Path};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone)]
 pub struct OptionData {
@@ -21,35 +22,105 @@ pub struct AttrTree {
     pub options: Vec<String>,
 }
 
+// Options taking a whole NixOS configuration, under which every option can be set again
+const NESTED: [[&str; 3]; 2] = [
+    ["containers", "<name>", "config"],
+    ["specialisation", "<name>", "configuration"],
+];
+
+/// Whether `pos` is an option taking a NixOS configuration of its own, such as
+/// `containers.<name>.config`. Both reference and concrete paths are accepted.
+pub fn isnested(pos: &[String]) -> bool {
+    NESTED
+        .iter()
+        .any(|x| pos.len() == 3 && pos[0] == x[0] && pos[2] == x[2])
+}
+
+/// Path of `pos` inside of the innermost nested configuration it is in,
+/// which is the path of the option it sets at the top level.
+pub fn innerpath(pos: &[String]) -> &[String] {
+    let mut pos = pos;
+    while pos.len() > 3 && isnested(&pos[..3]) {
+        pos = &pos[3..];
+    }
+    pos
+}
+
 pub fn read(file: &str) -> Result<(HashMap<String, OptionData>, AttrTree), Box<dyn Error>> {
-    let f = fs::read_to_string(file)?;
-    let data: HashMap<String, OptionData> = serde_json::from_str(&f)?;
+    let data = readdata(Path::new(file))?;
     let ops = data.keys().map(|x| x.as_str()).collect::<Vec<_>>();
     let tree = buildtree(ops)?;
     Ok((data, tree))
 }
 
+/// Reads the option data of an options file without building its tree.
+pub fn readdata(file: &Path) -> Result<HashMap<String, OptionData>, Box<dyn Error>> {
+    let f = fs::read_to_string(file)?;
+    Ok(serde_json::from_str(&f)?)
+}
+
+/// Finds the subtree at a reference path. The whole tree is found again under
+/// nested configurations, see [`isnested`].
 pub fn attrloc(tree: &AttrTree, pos: Vec<String>) -> Option<&AttrTree> {
-    match pos.len().cmp(&1) {
-        Ordering::Greater => match tree.attributes.get(&pos[0]) {
-            Some(x) => attrloc(x, pos[1..].to_vec()),
-            None => None,
-        },
-        Ordering::Equal => tree.attributes.get(&pos[0]),
-        Ordering::Less => Some(tree),
+    let mut t = tree;
+    // Start of the configuration `t` belongs to
+    let mut start = 0;
+    for (i, attr) in pos.iter().enumerate() {
+        if isnested(&pos[start..=i]) {
+            t = tree;
+            start = i + 1;
+        } else {
+            t = t.attributes.get(attr)?;
+        }
+    }
+    Some(t)
+}
+
+/// Finds the declared option a concrete path such as `users.users.alice.uid` belongs to,
+/// returning its reference path with `<name>` and `*` placeholders.
+pub fn refpath(tree: &AttrTree, pos: &[String]) -> Option<Vec<String>> {
+    let (last, parents) = pos.split_last()?;
+    let mut t = tree;
+    let mut out: Vec<String> = vec![];
+    // Start of the configuration `t` belongs to
+    let mut start = 0;
+    for attr in parents {
+        let mut next = out[start..].to_vec();
+        next.push(attr.to_string());
+        if isnested(&next) {
+            t = tree;
+            out.push(attr.to_string());
+            start = out.len();
+            continue;
+        }
+        let key = if t.attributes.contains_key(attr) {
+            attr.as_str()
+        } else if t.attributes.contains_key("<name>") {
+            "<name>"
+        } else if t.attributes.contains_key("*") && attr.parse::<usize>().is_ok() {
+            "*"
+        } else {
+            return None;
+        };
+        t = t.attributes.get(key)?;
+        out.push(key.to_string());
+    }
+    if t.options.contains(last) {
+        out.push(last.to_string());
+        Some(out)
+    } else {
+        None
     }
 }
 
-fn buildtree(ops: Vec<&str>) -> Result<AttrTree, Box<dyn Error>> {
-    let split = ops
-        .into_iter()
-        .map(|x| x.split('.').collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+pub fn buildtree(ops: Vec<&str>) -> Result<AttrTree, Box<dyn Error>> {
+    let split = ops.into_iter().map(optionpath::split).collect::<Vec<_>>();
     let mut tree = AttrTree {
         attributes: HashMap::new(),
         options: vec![],
     };
-    for attr in split {
+    for attr in &split {
+        let attr = attr.iter().map(|x| x.as_str()).collect::<Vec<_>>();
         match attr.len().cmp(&1) {
             Ordering::Greater => {
                 if tree.attributes.get(attr[0]).is_none() {
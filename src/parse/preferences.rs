@@ -1,6 +1,11 @@
-use anyhow::Result;
+use crate::config::APP_ID;
+use anyhow::{anyhow, Result};
+use gtk::{gio, prelude::*};
 use nix_data::config::configfile::NixDataConfig;
 
+// Key of the additional options files in GSettings
+const EXTRAOPTIONS: &str = "extra-options";
+
 pub fn getconfig() -> Option<NixDataConfig> {
     if let Ok(c) = nix_data::config::configfile::getconfig() {
         Some(c)
@@ -13,3 +18,24 @@ pub fn editconfig(config: NixDataConfig) -> Result<()> {
     nix_data::config::configfile::setuserconfig(config)?;
     Ok(())
 }
+
+/// Options files and directories of private modules, loaded along with the NixOS options.
+pub fn extraoptions() -> Vec<String> {
+    settings()
+        .and_then(|s| s.value(EXTRAOPTIONS).get::<Vec<String>>())
+        .unwrap_or_default()
+}
+
+pub fn setextraoptions(paths: &[String]) -> Result<()> {
+    let settings = settings().ok_or_else(|| anyhow!("GSettings schema {} not found", APP_ID))?;
+    settings.set_value(EXTRAOPTIONS, &paths.to_vec().to_variant())?;
+    Ok(())
+}
+
+/// The app's GSettings, `None` when its schema is not installed, such as when
+/// running from the build directory.
+pub fn settings() -> Option<gio::Settings> {
+    gio::SettingsSchemaSource::default()
+        .and_then(|x| x.lookup(APP_ID, true))
+        .map(|_| gio::Settings::new(APP_ID))
+}
@@ -0,0 +1,482 @@
+use super::optionpath;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+// Scores of a single query term, best match wins
+const SEGMENT_EXACT: usize = 100;
+const SEGMENT_PREFIX: usize = 60;
+const SEGMENT_FUZZY: usize = 40;
+const WORD_EXACT: usize = 50;
+const WORD_PREFIX: usize = 30;
+const DESC_EXACT: usize = 20;
+const DESC_PREFIX: usize = 15;
+const DESC_FUZZY: usize = 10;
+// Bonus for a term matching the last segment of an option
+const LAST_SEGMENT: usize = 10;
+
+/// An option name or description word an option is reachable by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// Whole path segment, byte range in the option name
+    Segment(usize, usize),
+    /// Word inside a camelCase segment, byte range in the option name
+    Word(usize, usize),
+    Description,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Posting {
+    option: usize,
+    field: Field,
+}
+
+/// Keys of the filters understood by [`Query`]
+pub const FILTERKEYS: [&str; 5] = ["type", "configured", "declared", "readonly", "default"];
+
+// Shorthands accepted by `type:`
+const TYPEALIASES: [(&str, &str); 9] = [
+    ("bool", "boolean"),
+    ("int", "integer"),
+    ("str", "string"),
+    ("attrs", "attribute set"),
+    ("list", "list of"),
+    ("enum", "one of"),
+    ("float", "floating point"),
+    ("pkg", "package"),
+    ("fn", "function"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Type description contains the value
+    Type(String),
+    /// Option is set in the configuration or has pending edits
+    Configured(bool),
+    /// A declaring module path contains the value
+    Declared(String),
+    ReadOnly(bool),
+    /// Option has a default value
+    Default(bool),
+}
+
+impl Filter {
+    /// Parses a `key:value` word, `None` if it is not a complete filter.
+    pub fn parse(word: &str) -> Option<Self> {
+        let (key, value) = word.split_once(':')?;
+        let flag = || match value.to_lowercase().as_str() {
+            "yes" | "y" | "true" | "1" => Some(true),
+            "no" | "n" | "false" | "0" => Some(false),
+            _ => None,
+        };
+        match key.to_lowercase().as_str() {
+            "type" if !value.is_empty() => Some(Filter::Type(value.to_lowercase())),
+            "configured" => flag().map(Filter::Configured),
+            "declared" if !value.is_empty() => {
+                Some(Filter::Declared(value.trim_end_matches('/').to_lowercase()))
+            }
+            "readonly" => flag().map(Filter::ReadOnly),
+            "default" => flag().map(Filter::Default),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, entry: &SearchEntry, configured: bool) -> bool {
+        match self {
+            Filter::Type(t) => {
+                let optype = entry.op_type.to_lowercase();
+                let t = TYPEALIASES
+                    .iter()
+                    .find(|(alias, _)| alias == t)
+                    .map(|(_, x)| *x)
+                    .unwrap_or(t);
+                optype.contains(t)
+            }
+            Filter::Configured(b) => configured == *b,
+            Filter::Declared(d) => entry
+                .declarations
+                .iter()
+                .any(|x| x.to_lowercase().contains(d.as_str())),
+            Filter::ReadOnly(b) => entry.read_only == *b,
+            Filter::Default(b) => entry.default == *b,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |b: &bool| if *b { "yes" } else { "no" };
+        match self {
+            Filter::Type(t) => write!(f, "type:{}", t),
+            Filter::Configured(b) => write!(f, "configured:{}", flag(b)),
+            Filter::Declared(d) => write!(f, "declared:{}", d),
+            Filter::ReadOnly(b) => write!(f, "readonly:{}", flag(b)),
+            Filter::Default(b) => write!(f, "default:{}", flag(b)),
+        }
+    }
+}
+
+/// A search query split into free text terms and filters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub terms: Vec<String>,
+    pub filters: Vec<Filter>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let mut out = Query::default();
+        for word in query.split_whitespace() {
+            if let Some(filter) = Filter::parse(word) {
+                out.filters.push(filter);
+            } else if let Some((key, _)) = word.split_once(':') {
+                // Filters that are still being typed should not restrict the results
+                if !FILTERKEYS.contains(&key.to_lowercase().as_str()) {
+                    out.terms.push(word.to_lowercase());
+                }
+            } else {
+                out.terms.push(word.to_lowercase());
+            }
+        }
+        out
+    }
+}
+
+/// Removes the first word of `query` that parses to `filter`.
+pub fn removefilter(query: &str, filter: &Filter) -> String {
+    let mut removed = false;
+    query
+        .split_whitespace()
+        .filter(|word| {
+            if !removed && Filter::parse(word).as_ref() == Some(filter) {
+                removed = true;
+                false
+            } else {
+                true
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Completions for the word being typed at the end of `query`.
+pub fn suggestions(query: &str) -> Vec<String> {
+    if query.is_empty() || query.ends_with(char::is_whitespace) {
+        return vec![];
+    }
+    let word = query.split_whitespace().last().unwrap_or_default();
+    match word.split_once(':') {
+        Some((key, value)) => {
+            let values: &[&str] = match key.to_lowercase().as_str() {
+                "type" => &[
+                    "bool", "int", "str", "attrs", "list", "enum", "path", "package", "port",
+                ],
+                "configured" | "readonly" | "default" => &["yes", "no"],
+                _ => &[],
+            };
+            values
+                .iter()
+                .filter(|x| x.starts_with(&value.to_lowercase()) && **x != value)
+                .map(|x| format!("{}:{}", key, x))
+                .collect()
+        }
+        None => FILTERKEYS
+            .iter()
+            .filter(|x| x.starts_with(&word.to_lowercase()))
+            .map(|x| format!("{}:", x))
+            .collect(),
+    }
+}
+
+/// Replaces the word being typed at the end of `query` with `word`.
+pub fn complete(query: &str, word: &str) -> String {
+    let start = query.rfind(char::is_whitespace).map(|x| x + 1).unwrap_or(0);
+    // Keys still need a value, values are done
+    let sep = if word.ends_with(':') { "" } else { " " };
+    format!("{}{}{}", &query[..start], word, sep)
+}
+
+/// Everything the search knows about an option.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchEntry {
+    pub name: String,
+    pub description: String,
+    pub op_type: String,
+    pub read_only: bool,
+    pub declarations: Vec<String>,
+    pub default: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    options: Vec<SearchEntry>,
+    postings: HashMap<String, Vec<Posting>>,
+    /// All keys of `postings`, sorted for prefix lookups
+    tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub name: String,
+    /// Byte ranges of `name` that matched the query
+    pub highlights: Vec<(usize, usize)>,
+}
+
+impl SearchIndex {
+    pub fn new(options: Vec<SearchEntry>) -> Self {
+        let mut index = SearchIndex::default();
+        for (i, mut entry) in options.into_iter().enumerate() {
+            let name = &entry.name;
+            // Quoted segments such as `"net.ipv4.ip_forward"` are indexed whole
+            for (start, end) in optionpath::segmentranges(name) {
+                let segment = &name[start..end];
+                index.add(segment.to_lowercase(), i, Field::Segment(start, end));
+                let words = camelwords(segment);
+                if words.len() > 1 {
+                    for (s, e) in words {
+                        index.add(
+                            segment[s..e].to_lowercase(),
+                            i,
+                            Field::Word(start + s, start + e),
+                        );
+                    }
+                }
+            }
+            let mut seen = HashSet::new();
+            for word in entry
+                .description
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|x| x.len() > 2)
+            {
+                let word = word.to_lowercase();
+                if seen.insert(word.clone()) {
+                    index.add(word, i, Field::Description);
+                }
+            }
+            // Only needed for the index
+            entry.description = String::new();
+            index.options.push(entry);
+        }
+        index.tokens = index.postings.keys().cloned().collect();
+        index.tokens.sort();
+        index
+    }
+
+    fn add(&mut self, token: String, option: usize, field: Field) {
+        self.postings
+            .entry(token)
+            .or_default()
+            .push(Posting { option, field });
+    }
+
+    /// Tokens starting with `prefix`.
+    fn prefixed<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a String> {
+        let start = self.tokens.partition_point(|x| x.as_str() < prefix);
+        self.tokens[start..]
+            .iter()
+            .take_while(move |x| x.starts_with(prefix))
+    }
+
+    /// Scores every option matching `term`, with the ranges of its name that matched.
+    fn matchterm(&self, term: &str) -> HashMap<usize, (usize, Vec<(usize, usize)>)> {
+        let mut out = HashMap::new();
+        for token in self.prefixed(term) {
+            let exact = token == term;
+            for posting in &self.postings[token] {
+                let (score, range) = match posting.field {
+                    Field::Segment(s, e) => {
+                        let mut score = if exact { SEGMENT_EXACT } else { SEGMENT_PREFIX };
+                        if self.options[posting.option].name.len() == e {
+                            score += LAST_SEGMENT;
+                        }
+                        (score, Some((s, e.min(s + term.len()))))
+                    }
+                    Field::Word(s, e) => (
+                        if exact { WORD_EXACT } else { WORD_PREFIX },
+                        Some((s, e.min(s + term.len()))),
+                    ),
+                    Field::Description if exact => (DESC_EXACT, None),
+                    Field::Description if term.len() > 2 => (DESC_PREFIX, None),
+                    Field::Description => continue,
+                };
+                record(&mut out, posting.option, score, range);
+            }
+        }
+
+        // Typo tolerance, only used if the term is long enough to be meaningful
+        let maxdist = match term.chars().count() {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+        if maxdist > 0 {
+            for token in &self.tokens {
+                if token.starts_with(term) || token.len().abs_diff(term.len()) > maxdist {
+                    continue;
+                }
+                if let Some(dist) = distance(term, token, maxdist) {
+                    for posting in &self.postings[token] {
+                        let (score, range) = match posting.field {
+                            Field::Segment(s, e) | Field::Word(s, e) => {
+                                (SEGMENT_FUZZY - 5 * dist, Some((s, e)))
+                            }
+                            Field::Description => (DESC_FUZZY - 3 * dist, None),
+                        };
+                        record(&mut out, posting.option, score, range);
+                    }
+                }
+            }
+        }
+
+        // Fall back to plain substring matches in the name
+        if out.is_empty() {
+            for (i, entry) in self.options.iter().enumerate() {
+                if let Some(s) = entry.name.to_lowercase().find(term) {
+                    record(&mut out, i, SEGMENT_FUZZY, Some((s, s + term.len())));
+                }
+            }
+        }
+        out
+    }
+
+    /// Finds options matching every term and filter of `query`, best matches first.
+    /// `configured` tells whether an option is set or has pending edits.
+    pub fn search(&self, query: &Query, configured: impl Fn(&str) -> bool) -> Vec<SearchResult> {
+        let allowed = |i: &usize| {
+            let entry = &self.options[*i];
+            query
+                .filters
+                .iter()
+                .all(|f| f.matches(entry, configured(&entry.name)))
+        };
+
+        if query.terms.is_empty() {
+            let mut out = (0..self.options.len())
+                .filter(|i| allowed(i))
+                .map(|i| &self.options[i])
+                .collect::<Vec<_>>();
+            out.sort_by(|a, b| a.name.len().cmp(&b.name.len()).then(a.name.cmp(&b.name)));
+            return out
+                .into_iter()
+                .map(|entry| SearchResult {
+                    name: entry.name.to_string(),
+                    highlights: vec![],
+                })
+                .collect();
+        }
+
+        let mut matches: Option<HashMap<usize, (usize, Vec<(usize, usize)>)>> = None;
+        for term in &query.terms {
+            // Path-like terms are matched segment by segment, keeping quoted names whole
+            let parts = optionpath::split(term)
+                .into_iter()
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<_>>();
+            for part in parts {
+                let termmatches = self.matchterm(&part);
+                matches = Some(match matches {
+                    None => termmatches,
+                    Some(prev) => prev
+                        .into_iter()
+                        .filter_map(|(i, (score, mut ranges))| {
+                            let (s, r) = termmatches.get(&i)?;
+                            for x in r {
+                                if !ranges.contains(x) {
+                                    ranges.push(*x);
+                                }
+                            }
+                            Some((i, (score + s, ranges)))
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        let mut ranked = matches
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(i, _)| allowed(i))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(a, (ascore, _)), (b, (bscore, _))| {
+            let (a, b) = (&self.options[*a].name, &self.options[*b].name);
+            bscore
+                .cmp(ascore)
+                .then(a.len().cmp(&b.len()))
+                .then(a.cmp(b))
+        });
+        ranked
+            .into_iter()
+            .map(|(i, (_, mut highlights))| {
+                highlights.sort_unstable();
+                SearchResult {
+                    name: self.options[i].name.to_string(),
+                    highlights,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Keeps the best score of an option and every range of its name that matched.
+fn record(
+    out: &mut HashMap<usize, (usize, Vec<(usize, usize)>)>,
+    option: usize,
+    score: usize,
+    range: Option<(usize, usize)>,
+) {
+    let entry = out.entry(option).or_insert((0, vec![]));
+    entry.0 = entry.0.max(score);
+    if let Some(r) = range {
+        if !entry.1.contains(&r) {
+            entry.1.push(r);
+        }
+    }
+}
+
+/// Byte ranges of the words in a camelCase or kebab-case name.
+fn camelwords(s: &str) -> Vec<(usize, usize)> {
+    let mut out = vec![];
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    for (i, c) in s.char_indices() {
+        if c == '-' || c == '_' {
+            if i > start {
+                out.push((start, i));
+            }
+            start = i + 1;
+        } else if c.is_uppercase() && prev.map(|p| p.is_lowercase()).unwrap_or(false) {
+            out.push((start, i));
+            start = i;
+        }
+        prev = Some(c);
+    }
+    if start < s.len() {
+        out.push((start, s.len()));
+    }
+    out
+}
+
+/// Levenshtein distance between `a` and `b`, `None` if it is larger than `max`.
+fn distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for i in 1..=a.len() {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        if cur.iter().min().copied().unwrap_or(0) > max {
+            return None;
+        }
+        prev = cur;
+    }
+    let d = prev[b.len()];
+    if d <= max {
+        Some(d)
+    } else {
+        None
+    }
+}
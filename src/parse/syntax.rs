@@ -0,0 +1,186 @@
+use rnix::{ParseError, SyntaxKind, SyntaxNode};
+use rowan::TextRange;
+use std::{error::Error, fmt, io};
+
+/// A syntax error in a Nix file, with the location of the offending node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// Line of the error, starting at 1
+    pub line: usize,
+    /// Column of the error, starting at 1
+    pub column: usize,
+    /// Text of the offending node, `None` if the file ended unexpectedly
+    pub node: Option<String>,
+    /// The offending line with surrounding context and a marker under the error
+    pub snippet: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}\n\n{}",
+            self.line, self.column, self.message, self.snippet
+        )
+    }
+}
+
+impl Error for SyntaxError {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// The configuration file itself does not parse
+    Syntax(SyntaxError),
+    /// The value given for an option does not parse
+    Value(String, SyntaxError),
+    /// The option could not be written to the configuration
+    Edit(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Syntax(e) => write!(f, "Syntax error. {}", e),
+            ConfigError::Value(op, e) => write!(f, "Invalid value for {}. {}", op, e),
+            ConfigError::Edit(op, reason) => write!(f, "Failed to edit {}: {}", op, reason),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<SyntaxError> for ConfigError {
+    fn from(e: SyntaxError) -> Self {
+        ConfigError::Syntax(e)
+    }
+}
+
+/// Parses Nix code, returning the first syntax error if there is any.
+pub fn parse(text: &str) -> Result<SyntaxNode, SyntaxError> {
+    let parse = rnix::Root::parse(text);
+    match parse.errors().first() {
+        Some(e) => Err(syntaxerror(text, e)),
+        None => Ok(parse.syntax()),
+    }
+}
+
+fn syntaxerror(text: &str, error: &ParseError) -> SyntaxError {
+    let range = match error {
+        ParseError::Unexpected(r)
+        | ParseError::UnexpectedExtra(r)
+        | ParseError::UnexpectedWanted(_, r, _)
+        | ParseError::UnexpectedDoubleBind(r)
+        | ParseError::DuplicatedArgs(r, _) => Some(*r),
+        _ => None,
+    };
+    let node = range.map(|r| text[r].to_string());
+    let shown = node
+        .as_ref()
+        .map(|x| x.lines().next().unwrap_or_default().to_string());
+    let message = match (error, &shown) {
+        (ParseError::UnexpectedWanted(_, _, wanted), Some(x)) => format!(
+            "unexpected `{}`, expected {}",
+            x,
+            wanted
+                .iter()
+                .map(|k| kindname(*k))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        ),
+        (ParseError::UnexpectedEOFWanted(wanted), _) => format!(
+            "unexpected end of file, expected {}",
+            wanted
+                .iter()
+                .map(|k| kindname(*k))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        ),
+        (ParseError::UnexpectedDoubleBind(_), Some(x)) => {
+            format!("`{}` is defined more than once", x)
+        }
+        (ParseError::DuplicatedArgs(_, name), _) => {
+            format!("argument `{}` is listed more than once", name)
+        }
+        (ParseError::UnexpectedExtra(_), Some(x)) => {
+            format!("unexpected `{}` after the end of the expression", x)
+        }
+        (ParseError::RecursionLimitExceeded, _) => String::from("expression is nested too deeply"),
+        (_, Some(x)) => format!("unexpected `{}`", x),
+        (_, None) => String::from("unexpected end of file"),
+    };
+    let range = range.unwrap_or_else(|| TextRange::empty((text.len() as u32).into()));
+    let (line, column) = linecol(text, usize::from(range.start()));
+    SyntaxError {
+        message,
+        line,
+        column,
+        node,
+        snippet: snippet(text, line, column, usize::from(range.len())),
+    }
+}
+
+fn kindname(kind: SyntaxKind) -> String {
+    match kind {
+        SyntaxKind::TOKEN_SEMICOLON => String::from("`;`"),
+        SyntaxKind::TOKEN_ASSIGN => String::from("`=`"),
+        SyntaxKind::TOKEN_COLON => String::from("`:`"),
+        SyntaxKind::TOKEN_COMMA => String::from("`,`"),
+        SyntaxKind::TOKEN_L_BRACE => String::from("`{`"),
+        SyntaxKind::TOKEN_R_BRACE => String::from("`}`"),
+        SyntaxKind::TOKEN_L_BRACK => String::from("`[`"),
+        SyntaxKind::TOKEN_R_BRACK => String::from("`]`"),
+        SyntaxKind::TOKEN_L_PAREN => String::from("`(`"),
+        SyntaxKind::TOKEN_R_PAREN => String::from("`)`"),
+        SyntaxKind::TOKEN_IN => String::from("`in`"),
+        SyntaxKind::TOKEN_THEN => String::from("`then`"),
+        SyntaxKind::TOKEN_ELSE => String::from("`else`"),
+        SyntaxKind::TOKEN_IDENT => String::from("a name"),
+        x => {
+            let name = format!("{:?}", x).to_lowercase();
+            name.trim_start_matches("token_")
+                .trim_start_matches("node_")
+                .replace('_', " ")
+        }
+    }
+}
+
+/// Converts a byte offset to a line and column, both starting at 1.
+pub fn linecol(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let linestart = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+    (line, before[linestart..].chars().count() + 1)
+}
+
+/// Shows `line` with one line of context on either side and marks `len` bytes at `column`.
+pub fn snippet(text: &str, line: usize, column: usize, len: usize) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    let first = line.saturating_sub(1).max(1);
+    let last = (line + 1).min(lines.len().max(line));
+    let width = last.to_string().len();
+    let mut out = vec![];
+    for n in first..=last {
+        let l = lines.get(n - 1).unwrap_or(&"");
+        out.push(format!("{:>width$} | {}", n, l, width = width));
+        if n == line {
+            let marklen = len.min(l.len().saturating_sub(column - 1)).max(1);
+            out.push(format!(
+                "{:>width$} | {}{}",
+                "",
+                " ".repeat(column - 1),
+                "^".repeat(marklen),
+                width = width
+            ));
+        }
+    }
+    out.join("\n")
+}
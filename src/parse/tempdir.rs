@@ -0,0 +1,57 @@
+use std::{
+    env, fs, io,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Names already taken by other users are skipped, up to this many times
+const MAXATTEMPTS: usize = 16;
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory in the temporary directory that only the current user can access,
+/// removed with everything inside of it when dropped.
+///
+/// Names in the shared temporary directory can be guessed by other users, so the
+/// directory is always newly created and never one that already exists.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> io::Result<Self> {
+        let mut attempts = 0;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.subsec_nanos())
+                .unwrap_or_default();
+            let path = env::temp_dir().join(format!(
+                "{}-{}-{}-{}",
+                prefix,
+                std::process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed),
+                nanos
+            ));
+            match fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(TempDir { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < MAXATTEMPTS => {
+                    attempts += 1
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,534 @@
+use super::bindings;
+use super::nixstring;
+use super::syntax;
+use rnix::ast;
+use rowan::ast::AstNode;
+
+/// Structure of an option type, read from the type description in the options file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionType {
+    Bool,
+    /// Integer within optional inclusive bounds
+    Int(Option<i64>, Option<i64>),
+    Str(StrRule),
+    Path,
+    /// Allowed values, written as in the type description
+    Enum(Vec<String>),
+    ListOf(Box<OptionType>),
+    AttrsOf(Box<OptionType>),
+    NullOr(Box<OptionType>),
+    /// Submodules, packages and other types only Nix can check
+    Other,
+}
+
+/// What a string option accepts beyond being a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrRule {
+    Any,
+    /// Not empty or only whitespace
+    NonEmpty,
+    /// No line breaks, a trailing one is allowed by some definitions of the type
+    SingleLine,
+    /// No line breaks except for one at the end
+    SingleLineTerminated,
+    /// No newlines or colons, as in entries of `/etc/passwd`
+    PasswdEntry,
+}
+
+/// Result of checking a value against an option type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+    Valid,
+    Invalid(String),
+    /// The value or type needs to be evaluated by Nix
+    Unknown,
+}
+
+// A value as far as it can be known without evaluating it
+enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float,
+    /// `None` for strings with interpolations
+    Str(Option<String>),
+    Path,
+    List(Vec<ast::Expr>),
+    /// `None` for sets whose attributes cannot be listed, like `rec` sets or sets with `inherit`
+    Attrs(Option<Vec<(String, ast::Expr)>>),
+    Lambda,
+    /// Variables, function calls and other expressions that need evaluating
+    Unknown,
+}
+
+impl OptionType {
+    pub fn parse(desc: &str) -> Self {
+        let desc = desc.trim();
+        // Newer nixpkgs puts parentheses around compound element types
+        if let Some(inner) = desc.strip_prefix('(').and_then(|x| x.strip_suffix(')')) {
+            if balanced(inner) {
+                return OptionType::parse(inner);
+            }
+        }
+        match desc {
+            "boolean" => return OptionType::Bool,
+            "signed integer" | "integer" => return OptionType::Int(None, None),
+            "unsigned integer, meaning >=0" => return OptionType::Int(Some(0), None),
+            "positive integer, meaning >0" => return OptionType::Int(Some(1), None),
+            "string" => return OptionType::Str(StrRule::Any),
+            "non-empty string" => return OptionType::Str(StrRule::NonEmpty),
+            "single-line string" => return OptionType::Str(StrRule::SingleLine),
+            "(optionally newline-terminated) single-line string" => {
+                return OptionType::Str(StrRule::SingleLineTerminated)
+            }
+            "string, not containing newlines or colons" => {
+                return OptionType::Str(StrRule::PasswdEntry)
+            }
+            "path" | "absolute path" => return OptionType::Path,
+            _ => {}
+        }
+        if desc.starts_with("strings concatenated with ") {
+            OptionType::Str(StrRule::Any)
+        } else if let Some(rest) = desc.strip_prefix("null or ") {
+            OptionType::NullOr(Box::new(OptionType::parse(rest)))
+        } else if let Some(rest) = desc.strip_prefix("list of ") {
+            OptionType::ListOf(Box::new(elemtype(rest)))
+        } else if let Some(rest) = desc
+            .strip_prefix("attribute set of ")
+            .or_else(|| desc.strip_prefix("lazy attribute set of "))
+        {
+            OptionType::AttrsOf(Box::new(elemtype(rest)))
+        } else if let Some(rest) = desc.strip_prefix("one of ") {
+            OptionType::Enum(enumvalues(rest))
+        } else if let Some(value) = desc
+            .strip_prefix("value ")
+            .and_then(|x| x.strip_suffix(" (singular enum)"))
+        {
+            OptionType::Enum(vec![value.to_string()])
+        } else if let Some(range) = desc
+            .strip_suffix(" (both inclusive)")
+            .filter(|_| desc.contains("integer"))
+            .and_then(|x| x.split_once("between "))
+            .and_then(|(_, x)| x.split_once(" and "))
+        {
+            match (range.0.parse::<i64>(), range.1.parse::<i64>()) {
+                (Ok(lo), Ok(hi)) => OptionType::Int(Some(lo), Some(hi)),
+                _ => OptionType::Other,
+            }
+        } else {
+            OptionType::Other
+        }
+    }
+
+    /// Checks a parsed value, falling back to `Unknown` wherever evaluation would be needed.
+    pub fn check(&self, expr: &ast::Expr) -> Validation {
+        if let OptionType::Other = self {
+            return Validation::Unknown;
+        }
+        let value = value(expr);
+        match (self, &value) {
+            (_, Value::Unknown) => Validation::Unknown,
+            (OptionType::NullOr(_), Value::Null) => Validation::Valid,
+            (OptionType::NullOr(t), _) => t.check(expr),
+            (OptionType::Bool, Value::Bool(_)) => Validation::Valid,
+            (OptionType::Int(lo, hi), Value::Int(n)) => {
+                if lo.map(|lo| *n < lo).unwrap_or(false) || hi.map(|hi| *n > hi).unwrap_or(false) {
+                    Validation::Invalid(format!("{} is not {}", n, self.describe()))
+                } else {
+                    Validation::Valid
+                }
+            }
+            (OptionType::Str(StrRule::Any), Value::Str(_)) => Validation::Valid,
+            (OptionType::Str(_), Value::Str(None)) => Validation::Unknown,
+            (OptionType::Str(rule), Value::Str(Some(s))) => match rule.accepts(s) {
+                Some(true) => Validation::Valid,
+                Some(false) => Validation::Invalid(format!(
+                    "{} is not {}",
+                    nixstring::encode(s),
+                    self.describe()
+                )),
+                None => Validation::Unknown,
+            },
+            (OptionType::Path, Value::Path) => Validation::Valid,
+            (OptionType::Path, Value::Str(Some(s))) => {
+                if s.starts_with('/') {
+                    Validation::Valid
+                } else {
+                    Validation::Invalid(format!("{} is not an absolute path", nixstring::encode(s)))
+                }
+            }
+            (OptionType::Path, Value::Str(None)) => Validation::Unknown,
+            (OptionType::Enum(_), Value::Str(None)) => Validation::Unknown,
+            (OptionType::Enum(values), v @ (Value::Str(_) | Value::Int(_) | Value::Bool(_))) => {
+                let shown = match v {
+                    Value::Str(Some(s)) => format!("\"{}\"", s),
+                    Value::Int(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => unreachable!(),
+                };
+                if values.contains(&shown) {
+                    Validation::Valid
+                } else {
+                    Validation::Invalid(format!("{} is not {}", shown, self.describe()))
+                }
+            }
+            (OptionType::ListOf(t), Value::List(items)) => combine(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (format!("Item {}", i + 1), t.check(x))),
+            ),
+            (OptionType::AttrsOf(_), Value::Attrs(None)) => Validation::Unknown,
+            (OptionType::AttrsOf(t), Value::Attrs(Some(attrs))) => combine(
+                attrs
+                    .iter()
+                    .map(|(name, x)| (format!("Attribute {}", name), t.check(x))),
+            ),
+            _ => Validation::Invalid(format!(
+                "Expected {}, got {}",
+                self.describe(),
+                describevalue(&value)
+            )),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            OptionType::Bool => String::from("a boolean"),
+            OptionType::Int(Some(lo), Some(hi)) => format!("an integer between {} and {}", lo, hi),
+            OptionType::Int(Some(lo), None) => format!("an integer of at least {}", lo),
+            OptionType::Int(None, Some(hi)) => format!("an integer of at most {}", hi),
+            OptionType::Int(None, None) => String::from("an integer"),
+            OptionType::Str(StrRule::Any) => String::from("a string"),
+            OptionType::Str(StrRule::NonEmpty) => String::from("a non-empty string"),
+            OptionType::Str(StrRule::SingleLine | StrRule::SingleLineTerminated) => {
+                String::from("a single-line string")
+            }
+            OptionType::Str(StrRule::PasswdEntry) => {
+                String::from("a string without newlines or colons")
+            }
+            OptionType::Path => String::from("an absolute path"),
+            OptionType::Enum(values) => format!("one of {}", values.join(", ")),
+            OptionType::ListOf(_) => String::from("a list"),
+            OptionType::AttrsOf(_) => String::from("an attribute set"),
+            OptionType::NullOr(t) => format!("null or {}", t.describe()),
+            OptionType::Other => String::from("a value of this type"),
+        }
+    }
+}
+
+impl StrRule {
+    // Mirrors the checks of the nixpkgs types with these descriptions, `None` if it cannot be told
+    fn accepts(&self, s: &str) -> Option<bool> {
+        let linebreak = |x: &str| x.contains(['\n', '\r']);
+        match self {
+            StrRule::Any => Some(true),
+            StrRule::NonEmpty => Some(!s.chars().all(|c| matches!(c, ' ' | '\t' | '\n'))),
+            StrRule::SingleLine => match s.strip_suffix('\n') {
+                _ if !linebreak(s) => Some(true),
+                // Only a trailing newline, which depends on how the type was defined
+                Some(x) if !linebreak(x) => None,
+                _ => Some(false),
+            },
+            StrRule::SingleLineTerminated => Some(!linebreak(s.strip_suffix('\n').unwrap_or(s))),
+            StrRule::PasswdEntry => Some(!s.contains([':', '\n'])),
+        }
+    }
+}
+
+/// Checks `value` against the type described by `op_type` without evaluating it.
+/// Values that do not parse are always invalid.
+pub fn validate(value: &str, op_type: &str) -> Validation {
+    let root = match syntax::parse(value) {
+        Ok(x) => x,
+        Err(e) => return Validation::Invalid(e.to_string()),
+    };
+    match ast::Root::cast(root).and_then(|x| x.expr()) {
+        Some(expr) => OptionType::parse(op_type).check(&expr),
+        None => Validation::Unknown,
+    }
+}
+
+fn value(expr: &ast::Expr) -> Value {
+    match expr {
+        ast::Expr::Paren(x) => x.expr().map(|x| value(&x)).unwrap_or(Value::Unknown),
+        ast::Expr::Ident(x) => match x.ident_token().as_ref().map(|t| t.text()) {
+            Some("null") => Value::Null,
+            Some("true") => Value::Bool(true),
+            Some("false") => Value::Bool(false),
+            _ => Value::Unknown,
+        },
+        ast::Expr::Literal(x) => match x.kind() {
+            ast::LiteralKind::Integer(_) => x
+                .syntax()
+                .to_string()
+                .parse::<i64>()
+                .map(Value::Int)
+                .unwrap_or(Value::Unknown),
+            ast::LiteralKind::Float(_) => Value::Float,
+            ast::LiteralKind::Uri(_) => Value::Str(Some(x.syntax().to_string())),
+        },
+        ast::Expr::UnaryOp(x) => match (x.operator(), x.expr().map(|x| value(&x))) {
+            (Some(ast::UnaryOpKind::Negate), Some(Value::Int(n))) => Value::Int(-n),
+            (Some(ast::UnaryOpKind::Negate), Some(Value::Float)) => Value::Float,
+            _ => Value::Unknown,
+        },
+        ast::Expr::Str(x) => Value::Str(nixstring::decode(&x.syntax().to_string())),
+        ast::Expr::Path(_) => Value::Path,
+        ast::Expr::List(x) => Value::List(x.items().collect()),
+        ast::Expr::AttrSet(x) => {
+            if x.rec_token().is_some() {
+                return Value::Attrs(None);
+            }
+            let mut attrs = vec![];
+            for entry in ast::HasEntry::entries(x) {
+                let entry = match entry {
+                    ast::Entry::AttrpathValue(entry) => entry,
+                    ast::Entry::Inherit(_) => return Value::Attrs(None),
+                };
+                match (bindings::attrnames(&entry), entry.value()) {
+                    (Some(names), Some(value)) if names.len() == 1 => {
+                        attrs.push((names[0].to_string(), value))
+                    }
+                    _ => return Value::Attrs(None),
+                }
+            }
+            Value::Attrs(Some(attrs))
+        }
+        ast::Expr::Lambda(_) => Value::Lambda,
+        _ => Value::Unknown,
+    }
+}
+
+fn describevalue(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Int(_) => "an integer",
+        Value::Float => "a float",
+        Value::Str(_) => "a string",
+        Value::Path => "a path",
+        Value::List(_) => "a list",
+        Value::Attrs(_) => "an attribute set",
+        Value::Lambda => "a function",
+        Value::Unknown => "an unknown value",
+    }
+}
+
+// The first invalid part decides, any part needing evaluation makes the whole value need it
+fn combine(results: impl Iterator<Item = (String, Validation)>) -> Validation {
+    let mut out = Validation::Valid;
+    for (name, result) in results {
+        match result {
+            Validation::Invalid(e) => return Validation::Invalid(format!("{}: {}", name, e)),
+            Validation::Unknown => out = Validation::Unknown,
+            Validation::Valid => {}
+        }
+    }
+    out
+}
+
+// Element types are plural in older nixpkgs, like `list of strings`
+fn elemtype(desc: &str) -> OptionType {
+    match OptionType::parse(desc) {
+        OptionType::Other if desc.ends_with('s') => OptionType::parse(&desc[..desc.len() - 1]),
+        t => t,
+    }
+}
+
+// Splits `"a", "b", 3` keeping the quotes, enum strings are shown unescaped
+fn enumvalues(desc: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = desc.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted && chars.peek() == Some(&' ') => {
+                chars.next();
+                out.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    out.push(current);
+    out
+}
+
+// Whether the parentheses of `s` all match, so that `(a) or (b)` is not taken for `(...)`
+fn balanced(s: &str) -> bool {
+    let mut depth = 0;
+    for c in s.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORT: &str = "16 bit unsigned integer; between 0 and 65535 (both inclusive)";
+
+    fn invalid(x: Validation) -> bool {
+        matches!(x, Validation::Invalid(_))
+    }
+
+    #[test]
+    fn parse_simple() {
+        assert_eq!(OptionType::parse("boolean"), OptionType::Bool);
+        assert_eq!(
+            OptionType::parse("signed integer"),
+            OptionType::Int(None, None)
+        );
+        assert_eq!(
+            OptionType::parse("unsigned integer, meaning >=0"),
+            OptionType::Int(Some(0), None)
+        );
+        assert_eq!(
+            OptionType::parse(PORT),
+            OptionType::Int(Some(0), Some(65535))
+        );
+        assert_eq!(OptionType::parse("string"), OptionType::Str(StrRule::Any));
+        assert_eq!(
+            OptionType::parse("strings concatenated with \"\\n\""),
+            OptionType::Str(StrRule::Any)
+        );
+        assert_eq!(
+            OptionType::parse("non-empty string"),
+            OptionType::Str(StrRule::NonEmpty)
+        );
+        assert_eq!(OptionType::parse("absolute path"), OptionType::Path);
+        assert_eq!(OptionType::parse("submodule"), OptionType::Other);
+        assert_eq!(OptionType::parse("package"), OptionType::Other);
+    }
+
+    #[test]
+    fn parse_compound() {
+        assert_eq!(
+            OptionType::parse("null or string"),
+            OptionType::NullOr(Box::new(OptionType::Str(StrRule::Any)))
+        );
+        assert_eq!(
+            OptionType::parse("list of strings"),
+            OptionType::ListOf(Box::new(OptionType::Str(StrRule::Any)))
+        );
+        assert_eq!(
+            OptionType::parse("list of (attribute set of boolean)"),
+            OptionType::ListOf(Box::new(OptionType::AttrsOf(Box::new(OptionType::Bool))))
+        );
+        assert_eq!(
+            OptionType::parse("lazy attribute set of (null or signed integer)"),
+            OptionType::AttrsOf(Box::new(OptionType::NullOr(Box::new(OptionType::Int(
+                None, None
+            )))))
+        );
+        assert_eq!(OptionType::parse("(boolean)"), OptionType::Bool);
+        // Not a single parenthesised type
+        assert_eq!(
+            OptionType::parse("(boolean) or (string)"),
+            OptionType::Other
+        );
+    }
+
+    #[test]
+    fn parse_enum() {
+        assert_eq!(
+            OptionType::parse("one of \"a\", \"b, c\", 3"),
+            OptionType::Enum(vec![
+                String::from("\"a\""),
+                String::from("\"b, c\""),
+                String::from("3")
+            ])
+        );
+        assert_eq!(
+            OptionType::parse("value \"x\" (singular enum)"),
+            OptionType::Enum(vec![String::from("\"x\"")])
+        );
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(validate("true", "boolean"), Validation::Valid);
+        assert_eq!(validate("(false)", "boolean"), Validation::Valid);
+        assert_eq!(validate("443", PORT), Validation::Valid);
+        assert_eq!(validate("-3", "signed integer"), Validation::Valid);
+        assert_eq!(validate("null", "null or string"), Validation::Valid);
+        assert_eq!(validate("\"x\"", "null or string"), Validation::Valid);
+        assert_eq!(
+            validate("[ \"a\" \"b\" ]", "list of string"),
+            Validation::Valid
+        );
+        assert_eq!(
+            validate("{ a = true; b = false; }", "attribute set of boolean"),
+            Validation::Valid
+        );
+        assert_eq!(validate("\"b\"", "one of \"a\", \"b\""), Validation::Valid);
+        assert_eq!(validate("./file", "path"), Validation::Valid);
+        assert_eq!(validate("\"/etc\"", "path"), Validation::Valid);
+        assert_eq!(validate("\"${x}\"", "string"), Validation::Valid);
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(invalid(validate("\"yes\"", "boolean")));
+        assert!(invalid(validate("70000", PORT)));
+        assert!(invalid(validate("-1", "unsigned integer, meaning >=0")));
+        assert!(invalid(validate("1.5", "signed integer")));
+        assert!(invalid(validate("[ \"a\" 1 ]", "list of string")));
+        assert!(invalid(validate("{ a = 1; }", "attribute set of boolean")));
+        assert!(invalid(validate("\"c\"", "one of \"a\", \"b\"")));
+        assert!(invalid(validate("\"etc\"", "path")));
+        assert!(invalid(validate("true", "null or string")));
+        // Values that do not parse
+        assert!(invalid(validate("1 +", "signed integer")));
+    }
+
+    #[test]
+    fn unknown_values() {
+        assert_eq!(validate("pkgs.hello", "boolean"), Validation::Unknown);
+        assert_eq!(validate("{ x = 1; }", "submodule"), Validation::Unknown);
+        assert_eq!(
+            validate("[ true x ]", "list of boolean"),
+            Validation::Unknown
+        );
+        assert_eq!(
+            validate("rec { a = true; }", "attribute set of boolean"),
+            Validation::Unknown
+        );
+        assert_eq!(validate("\"${x}\"", "path"), Validation::Unknown);
+    }
+
+    #[test]
+    fn constrained_strings() {
+        assert_eq!(validate("\"x\"", "non-empty string"), Validation::Valid);
+        assert!(invalid(validate("\" \\t\"", "non-empty string")));
+        assert!(invalid(validate("\"\"", "non-empty string")));
+        assert_eq!(validate("\"a\"", "single-line string"), Validation::Valid);
+        assert!(invalid(validate("\"a\\nb\"", "single-line string")));
+        assert_eq!(
+            validate("\"a\\n\"", "single-line string"),
+            Validation::Unknown
+        );
+        let terminated = "(optionally newline-terminated) single-line string";
+        assert_eq!(validate("\"a\\n\"", terminated), Validation::Valid);
+        assert!(invalid(validate("\"a\\r\\n\"", terminated)));
+        let passwd = "string, not containing newlines or colons";
+        assert_eq!(validate("\"user\"", passwd), Validation::Valid);
+        assert!(invalid(validate("\"a:b\"", passwd)));
+        // Interpolated strings are left to Nix
+        assert_eq!(
+            validate("\"${x}\"", "non-empty string"),
+            Validation::Unknown
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use super::window::AppMsg;
+use crate::config::APP_ID;
+use crate::parse::optionpath;
+use crate::parse::preferences;
+use adw::prelude::*;
+use gtk::gio;
+use log::{info, warn};
+use relm4::*;
+use std::collections::HashMap;
+
+// Number of recently visited options kept per configuration
+const MAXRECENT: usize = 20;
+
+type Saved = HashMap<String, Vec<(String, String)>>;
+
+#[tracker::track]
+pub struct BookmarksModel {
+    /// Configuration the lists belong to
+    profile: String,
+    /// Option currently shown and its reference path
+    current: Option<(String, String)>,
+    bookmarks: Vec<(String, String)>,
+    recent: Vec<(String, String)>,
+    #[tracker::no_eq]
+    settings: Option<gio::Settings>,
+}
+
+#[derive(Debug)]
+pub enum BookmarksMsg {
+    SetProfile(String),
+    Visit(Vec<String>, Vec<String>),
+    Leave,
+    ToggleBookmark,
+    RemoveBookmark(String),
+    Open(String, String),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for BookmarksModel {
+    type Init = ();
+    type Input = BookmarksMsg;
+    type Output = AppMsg;
+    type Widgets = BookmarksWidgets;
+
+    view! {
+        gtk::MenuButton {
+            set_icon_name: "starred-symbolic",
+            set_tooltip_text: Some("Bookmarks and recent options"),
+            #[wrap(Some)]
+            set_popover: popover = &gtk::Popover {
+                gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_propagate_natural_height: true,
+                    set_max_content_height: 500,
+                    set_min_content_width: 350,
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_margin_all: 10,
+                        set_spacing: 10,
+                        gtk::Button {
+                            #[watch]
+                            set_visible: model.current.is_some(),
+                            #[watch]
+                            set_label: if model.isbookmarked() { "Remove bookmark" } else { "Bookmark this option" },
+                            connect_clicked[sender] => move |_| {
+                                sender.input(BookmarksMsg::ToggleBookmark);
+                            }
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            add_css_class: "heading",
+                            set_label: "Bookmarks",
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            add_css_class: "dim-label",
+                            #[watch]
+                            set_visible: model.bookmarks.is_empty(),
+                            set_label: "No bookmarks",
+                        },
+                        #[name(bookmarklist)]
+                        gtk::ListBox {
+                            add_css_class: "boxed-list",
+                            set_selection_mode: gtk::SelectionMode::None,
+                            #[watch]
+                            set_visible: !model.bookmarks.is_empty(),
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            add_css_class: "heading",
+                            set_label: "Recent",
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            add_css_class: "dim-label",
+                            #[watch]
+                            set_visible: model.recent.is_empty(),
+                            set_label: "No recently visited options",
+                        },
+                        #[name(recentlist)]
+                        gtk::ListBox {
+                            add_css_class: "boxed-list",
+                            set_selection_mode: gtk::SelectionMode::None,
+                            #[watch]
+                            set_visible: !model.recent.is_empty(),
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    fn pre_view() {
+        let fill = |list: &gtk::ListBox, entries: &[(String, String)], removable: bool| {
+            while let Some(row) = list.first_child() {
+                list.remove(&row);
+            }
+            for (opt, refopt) in entries {
+                let row = adw::ActionRow::new();
+                row.set_use_markup(false);
+                row.set_title(opt);
+                row.set_activatable(true);
+                let opensender = sender.clone();
+                let (o, r) = (opt.to_string(), refopt.to_string());
+                let openpopover = popover.clone();
+                row.connect_activated(move |_| {
+                    openpopover.popdown();
+                    opensender.input(BookmarksMsg::Open(o.clone(), r.clone()));
+                });
+                if removable {
+                    let removebtn = gtk::Button::from_icon_name("user-trash-symbolic");
+                    removebtn.set_valign(gtk::Align::Center);
+                    removebtn.set_tooltip_text(Some("Remove bookmark"));
+                    removebtn.add_css_class("flat");
+                    let removesender = sender.clone();
+                    let o = opt.to_string();
+                    removebtn.connect_clicked(move |_| {
+                        removesender.input(BookmarksMsg::RemoveBookmark(o.clone()));
+                    });
+                    row.add_suffix(&removebtn);
+                }
+                list.append(&row);
+            }
+        };
+        if model.changed(BookmarksModel::bookmarks()) {
+            fill(bookmarklist, &model.bookmarks, true);
+        }
+        if model.changed(BookmarksModel::recent()) {
+            fill(recentlist, &model.recent, false);
+        }
+    }
+
+    fn init(
+        _parent_window: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let settings = preferences::settings();
+        if settings.is_none() {
+            warn!(
+                "GSettings schema {} not found, bookmarks will not be saved",
+                APP_ID
+            );
+        }
+        let model = BookmarksModel {
+            profile: String::new(),
+            current: None,
+            bookmarks: vec![],
+            recent: vec![],
+            settings,
+            tracker: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset();
+        match msg {
+            BookmarksMsg::SetProfile(profile) => {
+                info!("BookmarksMsg::SetProfile");
+                let bookmarks = self.load("bookmarks", &profile);
+                let recent = self.load("recent-options", &profile);
+                self.set_bookmarks(bookmarks);
+                self.set_recent(recent);
+                self.set_profile(profile);
+            }
+            BookmarksMsg::Visit(opt, refopt) => {
+                let entry = (optionpath::join(&opt), optionpath::join(&refopt));
+                self.update_recent(|x| {
+                    x.retain(|(o, _)| o != &entry.0);
+                    x.insert(0, entry.clone());
+                    x.truncate(MAXRECENT);
+                });
+                self.set_current(Some(entry));
+                self.save("recent-options", &self.recent);
+            }
+            BookmarksMsg::Leave => {
+                self.set_current(None);
+            }
+            BookmarksMsg::ToggleBookmark => {
+                info!("BookmarksMsg::ToggleBookmark");
+                if let Some(current) = self.current.clone() {
+                    if self.isbookmarked() {
+                        self.update_bookmarks(|x| x.retain(|(o, _)| o != &current.0));
+                    } else {
+                        self.update_bookmarks(|x| x.push(current));
+                    }
+                    self.save("bookmarks", &self.bookmarks);
+                }
+            }
+            BookmarksMsg::RemoveBookmark(opt) => {
+                info!("BookmarksMsg::RemoveBookmark");
+                self.update_bookmarks(|x| x.retain(|(o, _)| o != &opt));
+                self.save("bookmarks", &self.bookmarks);
+            }
+            BookmarksMsg::Open(opt, refopt) => {
+                info!("BookmarksMsg::Open");
+                let _ = sender.output(AppMsg::OpenOption(
+                    optionpath::split(&opt),
+                    optionpath::split(&refopt),
+                ));
+            }
+        }
+    }
+}
+
+impl BookmarksModel {
+    fn isbookmarked(&self) -> bool {
+        match &self.current {
+            Some((opt, _)) => self.bookmarks.iter().any(|(o, _)| o == opt),
+            None => false,
+        }
+    }
+
+    fn load(&self, key: &str, profile: &str) -> Vec<(String, String)> {
+        self.settings
+            .as_ref()
+            .and_then(|s| s.value(key).get::<Saved>())
+            .and_then(|mut x| x.remove(profile))
+            .unwrap_or_default()
+    }
+
+    fn save(&self, key: &str, entries: &[(String, String)]) {
+        if let Some(settings) = &self.settings {
+            let mut saved = settings.value(key).get::<Saved>().unwrap_or_default();
+            saved.insert(self.profile.to_string(), entries.to_vec());
+            if let Err(e) = settings.set_value(key, &saved.to_variant()) {
+                warn!("Failed to save {}: {}", key, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use super::effectivevalue::{flakedir, flakehost};
+use super::rebuild::RebuildMsg;
+use crate::parse::nixstring;
+use crate::parse::tempdir::TempDir;
+use log::{debug, info, warn};
+use nix_data::config::configfile::NixDataConfig;
+use relm4::*;
+use std::{fs, io, os::unix::fs::symlink, path::Path, process::Command};
+
+pub struct ConfigCheckHandler;
+
+#[derive(Debug)]
+pub enum ConfigCheckHandlerMsg {
+    Check(String, NixDataConfig),
+}
+
+impl Worker for ConfigCheckHandler {
+    type Init = ();
+    type Input = ConfigCheckHandlerMsg;
+    type Output = RebuildMsg;
+
+    fn init(_params: Self::Init, _sender: relm4::ComponentSender<Self>) -> Self {
+        Self
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            ConfigCheckHandlerMsg::Check(conf, config) => {
+                info!("Received Check message");
+                let _ = sender.output(RebuildMsg::DoneCheck(checkconfig(&conf, &config)));
+            }
+        }
+    }
+}
+
+/// Evaluates the system that `conf` would build without writing it in place.
+///
+/// The configuration directory, or the flake containing it, is copied to a
+/// temporary directory with `conf` written over the configuration file, so
+/// neither root nor a changed `/etc/nixos` is needed. Returns the derivation
+/// of the system on success.
+pub fn checkconfig(conf: &str, config: &NixDataConfig) -> Result<String, String> {
+    let path = Path::new(
+        config
+            .systemconfig
+            .as_deref()
+            .ok_or("No configuration file set")?,
+    );
+    let root = match &config.flake {
+        Some(flake) => flakedir(flake),
+        None => path.parent().unwrap_or_else(|| Path::new("/")),
+    };
+    let relpath = path.strip_prefix(root).map_err(|_| {
+        format!(
+            "{} is not inside the flake at {}",
+            path.to_string_lossy(),
+            root.to_string_lossy()
+        )
+    })?;
+    let tmpdir = TempDir::new("nce-check")
+        .map_err(|e| format!("Failed to create a temporary directory: {}", e))?;
+    let tmp = tmpdir.path().join("config");
+    copydir(root, &tmp)
+        .and_then(|_| fs::write(tmp.join(relpath), conf))
+        .map_err(|e| format!("Failed to copy {}: {}", root.to_string_lossy(), e))
+        .and_then(|_| evalsystem(&tmp, relpath, config))
+}
+
+fn evalsystem(dir: &Path, relpath: &Path, config: &NixDataConfig) -> Result<String, String> {
+    let output = if config.flake.is_some() {
+        // A path flake also picks up files that are not tracked by git yet
+        Command::new("nix")
+            .arg("--extra-experimental-features")
+            .arg("nix-command flakes")
+            .arg("eval")
+            .arg("--raw")
+            .arg("--no-write-lock-file")
+            .arg(format!(
+                "path:{}#nixosConfigurations.{}.config.system.build.toplevel.drvPath",
+                dir.to_string_lossy(),
+                nixstring::encode(&flakehost(config))
+            ))
+            .output()
+    } else {
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--expr")
+            .arg(format!(
+                "(import <nixpkgs/nixos> {{ configuration = /. + {}; }}).config.system.build.toplevel.drvPath",
+                nixstring::encode(&dir.join(relpath).to_string_lossy())
+            ))
+            .output()
+    };
+    match output {
+        Ok(output) if output.status.success() => {
+            let drv = String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .trim_matches('"')
+                .to_string();
+            debug!("drvPath: {}", drv);
+            Ok(drv)
+        }
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn copydir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // Not needed for evaluation and can be large
+        if name == ".git" {
+            continue;
+        }
+        let target = to.join(&name);
+        let filetype = entry.file_type()?;
+        if filetype.is_symlink() {
+            symlink(fs::read_link(entry.path())?, &target)?;
+        } else if filetype.is_dir() {
+            copydir(&entry.path(), &target)?;
+        } else if let Err(e) = fs::copy(entry.path(), &target) {
+            // Secrets readable only by root are not evaluated in most configurations
+            if e.kind() == io::ErrorKind::PermissionDenied {
+                warn!("Skipping {}: {}", entry.path().to_string_lossy(), e);
+            } else {
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::parse::evaluator::{self, EvalError};
+use crate::parse::nixpkgs::nixpkgspath;
+use crate::parse::nixstring;
+use crate::parse::optionpath;
+use crate::ui::optionpage::OptPageMsg;
+use log::{debug, info, warn};
+use nix_data::config::configfile::NixDataConfig;
+use relm4::*;
+use serde::Deserialize;
+use std::{
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+pub struct EvalAsyncHandler {
+    /// Number of the last evaluation requested, shared with the option page
+    latest: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+pub enum EvalAsyncHandlerMsg {
+    /// Evaluates an option, skipped if a later evaluation was requested meanwhile
+    Eval(usize, Vec<String>, NixDataConfig),
+    FindNixpkgs(NixDataConfig),
+}
+
+/// Value of an option after all modules are merged
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct EffectiveValue {
+    pub value: String,
+    /// Files containing a definition of the option
+    pub files: Vec<String>,
+}
+
+impl Worker for EvalAsyncHandler {
+    type Init = Arc<AtomicUsize>;
+    type Input = EvalAsyncHandlerMsg;
+    type Output = OptPageMsg;
+
+    fn init(latest: Self::Init, _sender: relm4::ComponentSender<Self>) -> Self {
+        Self { latest }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            EvalAsyncHandlerMsg::Eval(generation, opt, config) => {
+                info!("Received Eval message");
+                debug!("opt: {}", optionpath::join(&opt));
+                // The page has moved on to another option, which should not wait behind this one
+                if generation != self.latest.load(Ordering::SeqCst) {
+                    debug!("Skipping stale evaluation");
+                    return;
+                }
+                let out = evaluate(&opt, &config);
+                let _ = sender.output(OptPageMsg::DoneEval(opt, out));
+            }
+            EvalAsyncHandlerMsg::FindNixpkgs(config) => {
+                info!("Received FindNixpkgs message");
+                let host = flakehost(&config);
+                let _ = sender.output(OptPageMsg::SetNixpkgs(nixpkgspath(
+                    config
+                        .flake
+                        .as_deref()
+                        .map(|x| (flakedir(x), host.as_str())),
+                )));
+            }
+        }
+    }
+}
+
+/// Function taking an evaluated system and returning the value and definition files of `opt`.
+fn applyexpr(opt: &[String]) -> String {
+    format!(
+        "sys: let lib = sys.pkgs.lib; path = [ {} ]; in {{ value = lib.generators.toPretty {{ }} (lib.getAttrFromPath path sys.config); files = if lib.hasAttrByPath path sys.options then map toString (lib.getAttrFromPath path sys.options).files else [ ]; }}",
+        opt.iter().map(|x| nixstring::encode(x)).collect::<Vec<_>>().join(" ")
+    )
+}
+
+/// Directory of the flake, the flake setting may point at flake.nix itself.
+pub fn flakedir(flake: &str) -> &Path {
+    let flakepath = Path::new(flake);
+    if flakepath.is_file() {
+        flakepath.parent().unwrap_or(flakepath)
+    } else {
+        flakepath
+    }
+}
+
+/// Name of the `nixosConfigurations` entry to use.
+pub fn flakehost(config: &NixDataConfig) -> String {
+    match &config.flakearg {
+        Some(x) => x.to_string(),
+        None => gtk::glib::host_name().to_string(),
+    }
+}
+
+/// Expression evaluating to the system built from the configuration.
+fn systemexpr(config: &NixDataConfig) -> String {
+    if let Some(flake) = &config.flake {
+        format!(
+            "(builtins.getFlake {}).nixosConfigurations.{}",
+            nixstring::encode(&flakedir(flake).to_string_lossy()),
+            nixstring::encode(&flakehost(config))
+        )
+    } else {
+        format!(
+            "import <nixpkgs/nixos> {{ configuration = /. + {}; }}",
+            nixstring::encode(config.systemconfig.as_deref().unwrap_or_default())
+        )
+    }
+}
+
+fn evaluate(opt: &[String], config: &NixDataConfig) -> Result<EffectiveValue, String> {
+    match evaluator::eval(&systemexpr(config), &format!("({}) sys", applyexpr(opt))) {
+        Ok(x) => return serde_json::from_str::<EffectiveValue>(&x).map_err(|e| e.to_string()),
+        Err(EvalError::Eval(e)) => return Err(e),
+        Err(EvalError::Unavailable(e)) => {
+            warn!("Evaluator unavailable, evaluating directly: {}", e)
+        }
+    }
+    let expr = format!("({}) ({})", applyexpr(opt), systemexpr(config));
+    let output = if config.flake.is_some() {
+        Command::new("nix")
+            .arg("eval")
+            .arg("--impure")
+            .arg("--json")
+            .arg("--expr")
+            .arg(expr)
+            .output()
+    } else {
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--strict")
+            .arg("--json")
+            .arg("--expr")
+            .arg(expr)
+            .output()
+    };
+    match output {
+        Ok(output) => {
+            if output.status.success() {
+                serde_json::from_slice::<EffectiveValue>(&output.stdout).map_err(|e| e.to_string())
+            } else {
+                Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+            }
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
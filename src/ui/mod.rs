@@ -1,6 +1,10 @@
 mod about;
+mod bookmarks;
+mod configcheck;
+mod effectivevalue;
 mod nameentry;
 mod optionpage;
+mod overview;
 mod preferencespage;
 mod quitdialog;
 mod rebuild;
@@ -8,6 +12,7 @@ mod savechecking;
 mod searchentry;
 mod searchfactory;
 mod searchpage;
+mod sourcewindow;
 mod treefactory;
 mod welcome;
 pub mod window;
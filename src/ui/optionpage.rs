@@ -1,13 +1,31 @@
+use super::effectivevalue::*;
 use super::savechecking::*;
+use super::sourcewindow::openexternal;
 use super::window::*;
+use crate::parse::bindings::Modifiers;
+use crate::parse::nixpkgs;
+use crate::parse::nixstring;
+use crate::parse::optiondb::OptionDb;
+use crate::parse::optionpath;
 use crate::parse::options::OptionData;
+use crate::parse::syntax;
+use crate::parse::validate::{validate, OptionType, Validation};
 use adw::prelude::*;
 use html2pango;
 use log::*;
+use nix_data::config::configfile::NixDataConfig;
 use pandoc::{self, MarkdownExtension};
 use relm4::*;
 use sourceview5::prelude::*;
-use std::convert::identity;
+use std::{
+    convert::identity,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 #[derive(Debug)]
 pub enum OptPageMsg {
@@ -16,36 +34,69 @@ pub enum OptPageMsg {
         Vec<String>,
         Vec<String>,
         String,
-        Vec<String>,
+        String,
+        Modifiers,
     ),
     UpdateConf(String),
     UpdateConfMod(String),
+    UpdateNote(String),
     ResetConf,
     ClearConf,
     SaveConf,
     DoneSaving(bool, String),
+    DoneEval(Vec<String>, Result<EffectiveValue, String>),
+    SetConfig(NixDataConfig),
+    SetDb(Arc<OptionDb>),
+    SetNixpkgs(Option<PathBuf>),
+    ShowDeclaration(String),
+    OpenDeclaration(String),
     SetScheme(String),
+    /// Set a nullable option to `null`, or back to its value
+    SetNull(bool),
+    /// Edit strings as several lines of text
+    SetMultiline(bool),
+    /// An option was saved from another page
+    Edited(String, String),
+    EditedNote(String, String),
 }
 
 #[tracker::track]
 pub struct OptPageModel {
+    /// Tab the page is shown in
+    id: usize,
     pub opt: Vec<String>,
     pub refopt: Vec<String>,
     pub data: OptionData,
     pub conf: String,
     pub modifiedconf: String,
-    alloptions: Vec<String>,
+    pub note: String,
+    pub modifiednote: String,
+    pub modifiers: Modifiers,
+    /// Whether strings are edited in a text view and written as indented strings
+    multiline: bool,
+    #[tracker::no_eq]
+    db: Arc<OptionDb>,
     scheme: Option<sourceview5::StyleScheme>,
     saving: bool,
     resettracker: u8,
     valuetracker: u8,
+    evaluating: bool,
+    effective: Option<Result<EffectiveValue, String>>,
+    nixpkgs: Option<PathBuf>,
+    #[tracker::no_eq]
+    config: Option<NixDataConfig>,
     #[tracker::no_eq]
     async_handler: WorkerController<SaveAsyncHandler>,
+    #[tracker::no_eq]
+    eval_handler: WorkerController<EvalAsyncHandler>,
+    /// Number of the last evaluation sent to `eval_handler`
+    #[tracker::no_eq]
+    evalgeneration: Arc<AtomicUsize>,
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for OptPageModel {
-    type Init = ();
+    type Init = usize;
     type Input = OptPageMsg;
     type Output = AppMsg;
     type Widgets = OptPageWidgets;
@@ -65,7 +116,7 @@ impl SimpleComponent for OptPageModel {
                         set_halign: gtk::Align::Start,
                         add_css_class: "title-1",
                         #[watch]
-                        set_label: &model.opt.join(".")
+                        set_label: &optionpath::join(&model.opt)
                     },
 
                     gtk::Box {
@@ -140,6 +191,56 @@ impl SimpleComponent for OptPageModel {
                         },
                     },
 
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        #[watch]
+                        set_visible: model.modifiers.priority.is_some(),
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            add_css_class: "header",
+                            add_css_class: "single-line",
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                                set_label: "Priority",
+                            }
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            set_wrap: true,
+                            add_css_class: "body",
+                            add_css_class: "monospace",
+                            #[watch]
+                            set_label: model.modifiers.priority.as_deref().unwrap_or_default(),
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        #[watch]
+                        set_visible: !model.modifiers.conditions.is_empty(),
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            add_css_class: "header",
+                            add_css_class: "single-line",
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                                set_label: "Only set if",
+                            }
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            set_wrap: true,
+                            add_css_class: "body",
+                            add_css_class: "monospace",
+                            #[watch]
+                            set_label: &model.modifiers.conditions.join("\nand "),
+                        },
+                    },
+
                     gtk::Box {
                         set_orientation: gtk::Orientation::Vertical,
                         set_spacing: 10,
@@ -244,13 +345,117 @@ impl SimpleComponent for OptPageModel {
                             },
                         },
                     },
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        #[watch]
+                        set_visible: !model.data.declarations.is_empty(),
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            add_css_class: "header",
+                            add_css_class: "single-line",
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                                set_label: "Declared in",
+                            }
+                        },
+                        #[name(declbox)]
+                        gtk::ListBox {
+                            add_css_class: "boxed-list",
+                            set_selection_mode: gtk::SelectionMode::None,
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        #[watch]
+                        set_visible: model.evaluating || model.effective.is_some(),
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            add_css_class: "header",
+                            add_css_class: "single-line",
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                                set_label: "Effective value",
+                            },
+                            gtk::Spinner {
+                                set_halign: gtk::Align::End,
+                                set_hexpand: true,
+                                #[watch]
+                                set_spinning: model.evaluating,
+                                #[watch]
+                                set_visible: model.evaluating,
+                            }
+                        },
+                        gtk::Frame {
+                            add_css_class: "code",
+                            #[watch]
+                            set_visible: matches!(model.effective, Some(Ok(_))),
+                            sourceview5::View {
+                                set_editable: false,
+                                set_monospace: true,
+                                set_cursor_visible: false,
+                                set_top_margin: 5,
+                                set_bottom_margin: 5,
+                                set_left_margin: 5,
+                                #[wrap(Some)]
+                                set_buffer: effectivebuf = &sourceview5::Buffer {
+                                    #[track(model.changed(OptPageModel::scheme()))]
+                                    set_style_scheme: model.scheme.as_ref(),
+                                    #[track(model.changed(OptPageModel::effective()))]
+                                    set_text: match &model.effective {
+                                        Some(Ok(x)) => x.value.as_str(),
+                                        _ => "",
+                                    },
+                                }
+                            },
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            set_wrap: true,
+                            set_selectable: true,
+                            add_css_class: "dim-label",
+                            add_css_class: "monospace",
+                            #[watch]
+                            set_visible: matches!(model.effective, Some(Err(_))),
+                            #[track(model.changed(OptPageModel::effective()))]
+                            set_label: match &model.effective {
+                                Some(Err(e)) => e.as_str(),
+                                _ => "",
+                            },
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            add_css_class: "heading",
+                            #[watch]
+                            set_visible: matches!(&model.effective, Some(Ok(x)) if !x.files.is_empty()),
+                            set_label: "Defined in",
+                        },
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            set_wrap: true,
+                            set_selectable: true,
+                            add_css_class: "body",
+                            add_css_class: "monospace",
+                            #[watch]
+                            set_visible: matches!(&model.effective, Some(Ok(x)) if !x.files.is_empty()),
+                            #[track(model.changed(OptPageModel::effective()))]
+                            set_label: &match &model.effective {
+                                Some(Ok(x)) => x.files.join("\n"),
+                                _ => String::new(),
+                            },
+                        },
+                    },
                     gtk::Separator {
                         set_opacity: 0.0,
                         set_margin_top: 5,
                     },
                     gtk::Box {
                         #[watch]
-                        set_visible: valuestack.is_child_visible(),
+                        set_visible: valuestack.is_child_visible() || model.nullable(),
                         set_orientation: gtk::Orientation::Vertical,
                         set_spacing: 10,
                         #[name(simplevalue_box)]
@@ -262,10 +467,26 @@ impl SimpleComponent for OptPageModel {
                                 set_halign: gtk::Align::Start,
                                 add_css_class: "heading",
                                 set_label: "Value",
-                            }
+                            },
+                            #[name(nullbtn)]
+                            append = &gtk::ToggleButton {
+                                set_halign: gtk::Align::End,
+                                set_hexpand: true,
+                                set_label: "null",
+                                set_tooltip_text: Some("Set to null, which unlike clearing the value overrides the default"),
+                                #[watch]
+                                set_visible: model.nullable(),
+                                #[watch]
+                                set_active: model.isnull(),
+                                connect_toggled[sender] => move |x| {
+                                    sender.input(OptPageMsg::SetNull(x.is_active()))
+                                },
+                            },
                         },
                         #[name(valuestack)]
                         gtk::Stack {
+                            #[watch]
+                            set_sensitive: !model.isnull(),
                             #[name(number)]
                             gtk::SpinButton {
                                 set_halign: gtk::Align::Start,
@@ -278,13 +499,51 @@ impl SimpleComponent for OptPageModel {
                                     }
                                 },
                             },
-                            #[name(stringentry)]
-                            gtk::Entry {
-                                set_halign: gtk::Align::Start,
-                                connect_changed[sender] => move |x| {
-                                    if x.is_sensitive() {
-                                        sender.input(OptPageMsg::UpdateConfMod(format!("\"{}\"", x.text())));
-                                    }
+                            #[name(stringbox)]
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 5,
+                                #[name(stringentry)]
+                                gtk::Entry {
+                                    set_halign: gtk::Align::Start,
+                                    #[watch]
+                                    set_visible: !model.multiline,
+                                    connect_changed[sender] => move |x| {
+                                        if x.is_sensitive() {
+                                            sender.input(OptPageMsg::UpdateConfMod(nixstring::encode(&x.text())));
+                                        }
+                                    },
+                                },
+                                gtk::Frame {
+                                    add_css_class: "code",
+                                    #[watch]
+                                    set_visible: model.multiline,
+                                    #[name(stringview)]
+                                    gtk::TextView {
+                                        set_height_request: 100,
+                                        set_monospace: true,
+                                        set_top_margin: 5,
+                                        set_bottom_margin: 5,
+                                        set_left_margin: 5,
+                                        #[wrap(Some)]
+                                        set_buffer: stringbuf = &gtk::TextBuffer {
+                                            connect_changed[sender, stringview] => move |x| {
+                                                if stringview.is_sensitive() {
+                                                    let (start, end) = x.bounds();
+                                                    let text = x.text(&start, &end, true);
+                                                    sender.input(OptPageMsg::UpdateConfMod(nixstring::encodeindented(&text)));
+                                                }
+                                            },
+                                        },
+                                    },
+                                },
+                                gtk::CheckButton {
+                                    set_label: Some("Multi-line"),
+                                    #[watch]
+                                    set_active: model.multiline,
+                                    connect_toggled[sender] => move |x| {
+                                        sender.input(OptPageMsg::SetMultiline(x.is_active()));
+                                    },
                                 },
                             },
                             #[name(truefalse)]
@@ -310,11 +569,6 @@ impl SimpleComponent for OptPageModel {
                                         }
                                     }
                                 },
-                                // #[name(nullbtn)]
-                                // gtk::ToggleButton {
-                                //     set_label: "null",
-                                //     set_group: Some(&truebtn),
-                                // }
                             },
                         }
                     },
@@ -371,13 +625,41 @@ impl SimpleComponent for OptPageModel {
                                 }
                             },
                         },
+                        #[name(validationlabel)]
+                        gtk::Label {
+                            set_halign: gtk::Align::Start,
+                            set_wrap: true,
+                            set_selectable: true,
+                            add_css_class: "error",
+                            set_visible: false,
+                        },
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            add_css_class: "header",
+                            add_css_class: "single-line",
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                                set_label: "Note",
+                            }
+                        },
+                        gtk::Entry {
+                            set_placeholder_text: Some("Comment written above this option"),
+                            #[track(model.changed(OptPageModel::opt()))]
+                            set_text: &model.note,
+                            #[track(model.changed(OptPageModel::resettracker()))]
+                            set_text: &model.modifiednote,
+                            connect_changed[sender] => move |x| {
+                                sender.input(OptPageMsg::UpdateNote(x.text().to_string()))
+                            },
+                        },
                         gtk::Box {
                             set_orientation: gtk::Orientation::Horizontal,
                             set_spacing: 10,
                             gtk::Button {
                                 set_label: "Reset",
                                 #[watch]
-                                set_sensitive: model.conf != model.modifiedconf,
+                                set_sensitive: model.conf != model.modifiedconf || model.note != model.modifiednote,
                                 connect_clicked[sender] => move |_| {
                                     sender.input(OptPageMsg::ResetConf)
                                 }
@@ -399,7 +681,7 @@ impl SimpleComponent for OptPageModel {
                                     set_label: "Save",
                                     add_css_class: "suggested-action",
                                     #[watch]
-                                    set_sensitive: model.conf != model.modifiedconf,
+                                    set_sensitive: model.conf != model.modifiedconf || model.note != model.modifiednote,
                                     connect_clicked[sender] => move |_| {
                                         sender.input(OptPageMsg::SaveConf)
                                     },
@@ -419,10 +701,9 @@ impl SimpleComponent for OptPageModel {
 
     fn pre_view() {
         info!("pre_view");
-        let set_val = || {
+        let set_val = |val: &str| {
             debug!("SET VAL");
             if let Some(x) = valuestack.visible_child() {
-                let val = model.conf.as_str();
                 if x.eq(truefalse) {
                     if val == "true" {
                         truebtn.set_active(true);
@@ -442,22 +723,15 @@ impl SimpleComponent for OptPageModel {
                         number.set_value(0.0);
                     }
                     number.set_sensitive(true);
-                } else if x.eq(stringentry) {
-                    if let Some(x) = val.chars().next() {
-                        if let Some(y) = val.chars().last() {
-                            if x == '"' && y == '"' {
-                                if let Some(v) = val.get(1..val.len() - 1) {
-                                    stringentry.set_sensitive(false);
-                                    stringentry.set_text(v);
-                                    stringentry.set_sensitive(true);
-                                    return;
-                                }
-                            }
-                        }
-                    }
+                } else if x.eq(stringbox) {
+                    // Values that are not plain strings, like interpolations, are left to the code editor
+                    let text = nixstring::decode(val).unwrap_or_default();
                     stringentry.set_sensitive(false);
-                    stringentry.set_text("");
+                    stringentry.set_text(&text);
                     stringentry.set_sensitive(true);
+                    stringview.set_sensitive(false);
+                    stringbuf.set_text(&text);
+                    stringview.set_sensitive(true);
                 } else {
                     warn!("Unhandled valuestack child {:?}", x);
                 }
@@ -470,23 +744,74 @@ impl SimpleComponent for OptPageModel {
         };
 
         if model.changed(OptPageModel::opt()) {
-            let optype = model.data.op_type.as_str();
+            let optype = match OptionType::parse(model.data.op_type.as_str()) {
+                OptionType::NullOr(t) => *t,
+                t => t,
+            };
             valuestack.set_child_visible(true);
             match optype {
-                "boolean" | "null or boolean" => valuestack.set_visible_child(truefalse),
-                "signed integer" | "null or signed integer" => valuestack.set_visible_child(number),
-                "string" | "null or string" | "string, not containing newlines or colons" => {
-                    valuestack.set_visible_child(stringentry)
-                }
+                OptionType::Bool => valuestack.set_visible_child(truefalse),
+                OptionType::Int(..) => valuestack.set_visible_child(number),
+                OptionType::Str(_) => valuestack.set_visible_child(stringbox),
                 _ => valuestack.set_child_visible(false),
             }
             if valuestack.is_child_visible() {
-                set_val();
+                set_val(&model.conf);
             }
         }
         if model.changed(OptPageModel::resettracker()) {
-            // Reset button is pressed
-            set_val();
+            // Reset button is pressed or the value is set from outside the editors
+            set_val(&model.modifiedconf);
+        }
+        if model.changed(OptPageModel::modifiedconf()) || model.changed(OptPageModel::data()) {
+            // Checked as the value is typed, types that need Nix are checked on save
+            match validate(&model.modifiedconf, model.data.op_type.as_str()) {
+                Validation::Invalid(e) if !model.modifiedconf.trim().is_empty() => {
+                    validationlabel.set_label(&e);
+                    validationlabel.set_visible(true);
+                }
+                _ => validationlabel.set_visible(false),
+            }
+        }
+        if model.changed(OptPageModel::multiline()) {
+            // Show the edited text in the newly shown string editor
+            set_val(&model.modifiedconf);
+        }
+        if model.changed(OptPageModel::data()) || model.changed(OptPageModel::nixpkgs()) {
+            while let Some(row) = declbox.first_child() {
+                declbox.remove(&row);
+            }
+            for decl in model.data.declarations.iter() {
+                let decl = decl.as_str().to_string();
+                let found = nixpkgs::resolve(model.nixpkgs.as_deref(), &decl)
+                    .map(|x| x.exists())
+                    .unwrap_or(false);
+                let row = adw::ActionRow::new();
+                row.set_use_markup(false);
+                row.set_title(&decl);
+                let viewbtn = gtk::Button::from_icon_name("document-open-symbolic");
+                viewbtn.set_valign(gtk::Align::Center);
+                viewbtn.set_tooltip_text(Some("View source"));
+                viewbtn.add_css_class("flat");
+                viewbtn.set_sensitive(found);
+                let viewsender = sender.clone();
+                let viewdecl = decl.clone();
+                viewbtn.connect_clicked(move |_| {
+                    viewsender.input(OptPageMsg::ShowDeclaration(viewdecl.clone()));
+                });
+                let editbtn = gtk::Button::from_icon_name("document-edit-symbolic");
+                editbtn.set_valign(gtk::Align::Center);
+                editbtn.set_tooltip_text(Some("Open in editor"));
+                editbtn.add_css_class("flat");
+                editbtn.set_sensitive(found);
+                let editsender = sender.clone();
+                editbtn.connect_clicked(move |_| {
+                    editsender.input(OptPageMsg::OpenDeclaration(decl.clone()));
+                });
+                row.add_suffix(&viewbtn);
+                row.add_suffix(&editbtn);
+                declbox.append(&row);
+            }
         }
         if model.saving {
             savestack.set_visible_child(spinner)
@@ -496,25 +821,40 @@ impl SimpleComponent for OptPageModel {
     }
 
     fn init(
-        _parent_window: Self::Init,
+        id: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let async_handler = SaveAsyncHandler::builder()
             .detach_worker(())
             .forward(sender.input_sender(), identity);
+        let evalgeneration = Arc::new(AtomicUsize::new(0));
+        let eval_handler = EvalAsyncHandler::builder()
+            .detach_worker(evalgeneration.clone())
+            .forward(sender.input_sender(), identity);
         let model = OptPageModel {
+            id,
             opt: vec![],    //parent_window.position.clone(),
             refopt: vec![], //parent_window.refposition.clone(),
             data: OptionData::default(),
             conf: String::new(),
             modifiedconf: String::new(),
+            note: String::new(),
+            modifiednote: String::new(),
+            modifiers: Modifiers::default(),
+            multiline: false,
             saving: false,
-            alloptions: vec![], //parent_window.data.keys().map(|x| x.to_string()).collect::<Vec<String>>(),
+            db: Arc::new(OptionDb::default()),
             scheme: None,
             resettracker: 0,
             valuetracker: 0,
+            evaluating: false,
+            effective: None,
+            nixpkgs: None,
+            config: None,
             async_handler,
+            eval_handler,
+            evalgeneration,
             tracker: 0,
         };
 
@@ -526,16 +866,29 @@ impl SimpleComponent for OptPageModel {
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         self.reset();
         match msg {
-            OptPageMsg::UpdateOption(data, opt, refopt, conf, alloptions) => {
+            OptPageMsg::UpdateOption(data, opt, refopt, conf, note, modifiers) => {
                 info!("OptPageMsg::UpdateOption");
                 self.update_conf(|x| x.clear());
                 self.update_modifiedconf(|x| x.clear());
                 self.set_data(*data);
+                self.set_note(note.clone());
+                self.set_modifiednote(note);
+                self.set_modifiers(modifiers);
                 self.update_opt(|o| *o = opt.to_vec());
                 self.set_refopt(refopt);
+                self.set_multiline(nixstring::ismultiline(&conf));
                 self.set_conf(conf.clone());
                 self.set_modifiedconf(conf);
-                self.set_alloptions(alloptions);
+                self.set_effective(None);
+                if let Some(config) = &self.config {
+                    self.set_evaluating(true);
+                    let generation = self.evalgeneration.fetch_add(1, Ordering::SeqCst) + 1;
+                    self.eval_handler.emit(EvalAsyncHandlerMsg::Eval(
+                        generation,
+                        opt,
+                        config.clone(),
+                    ));
+                }
             }
             OptPageMsg::UpdateConf(conf) => {
                 info!("OptPageMsg::UpdateConf");
@@ -550,10 +903,19 @@ impl SimpleComponent for OptPageModel {
                     self.update_valuetracker(|_| ()); // Simulate change to conf
                 }
             }
+            OptPageMsg::UpdateNote(note) => {
+                info!("OptPageMsg::UpdateNote");
+                if note != self.modifiednote {
+                    self.set_modifiednote(note);
+                }
+            }
             OptPageMsg::ResetConf => {
                 info!("OptPageMsg::ResetConf");
                 let conf = self.conf.clone();
+                self.set_multiline(nixstring::ismultiline(&conf));
                 self.set_modifiedconf(conf);
+                let note = self.note.clone();
+                self.set_modifiednote(note);
                 self.update_valuetracker(|_| ()); // Simulate change to conf
                 self.update_resettracker(|_| ()); // Simulate reset
             }
@@ -564,8 +926,18 @@ impl SimpleComponent for OptPageModel {
             }
             OptPageMsg::SaveConf => {
                 info!("OptPageMsg::SaveConf");
-                let opt = self.opt.join(".");
-                let refopt = self.refopt.join(".");
+                let opt = optionpath::join(&self.opt);
+                let refopt = optionpath::join(&self.refopt);
+                if self.note != self.modifiednote {
+                    // Notes are plain comments and need no type checking
+                    let note = self.modifiednote.trim().to_string();
+                    self.set_note(note.clone());
+                    self.set_modifiednote(note.clone());
+                    let _ = sender.output(AppMsg::EditNote(opt.clone(), note));
+                    if self.conf == self.modifiedconf {
+                        return;
+                    }
+                }
                 let mut conf = self.modifiedconf.clone();
                 while conf.ends_with('\n') || conf.ends_with(' ') {
                     conf.pop();
@@ -573,15 +945,48 @@ impl SimpleComponent for OptPageModel {
                 self.set_modifiedconf(conf.clone());
                 if conf.is_empty() {
                     sender.input(OptPageMsg::DoneSaving(true, "true\n".to_string()));
+                } else if let Err(e) = syntax::parse(&conf) {
+                    // No need to ask nix if the value does not even parse
+                    let _ = sender.output(AppMsg::SaveError(self.id, e.to_string()));
                 } else {
-                    self.set_saving(true);
-                    let _ = sender.output(AppMsg::SetBusy(true));
-                    self.async_handler.emit(SaveAsyncHandlerMsg::SaveCheck(
-                        opt,
-                        refopt,
-                        conf,
-                        self.alloptions.to_vec(),
-                    ));
+                    // Only submodules and custom types need a round trip through nix
+                    match validate(&conf, self.data.op_type.as_str()) {
+                        Validation::Valid => {
+                            sender.input(OptPageMsg::DoneSaving(true, "true\n".to_string()))
+                        }
+                        Validation::Invalid(e) => {
+                            let _ = sender.output(AppMsg::SaveError(self.id, e));
+                        }
+                        // Options from additional files are not declared in the nixpkgs the check
+                        // evaluates, so only what could be validated above is checked
+                        Validation::Unknown if self.db.source(&refopt).is_some() => {
+                            sender.input(OptPageMsg::DoneSaving(true, "true\n".to_string()))
+                        }
+                        Validation::Unknown => match &self.config {
+                            Some(config) => {
+                                self.async_handler.emit(SaveAsyncHandlerMsg::SaveCheck(
+                                    opt,
+                                    refopt,
+                                    conf,
+                                    self.db.clone(),
+                                    config.clone(),
+                                ));
+                                self.set_saving(true);
+                                let _ = sender.output(AppMsg::SetBusy(true));
+                            }
+                            None => {
+                                warn!("No configuration to check {} against", opt);
+                                sender.input(OptPageMsg::DoneSaving(
+                                    false,
+                                    format!(
+                                        "{} could not be checked against {} without a configuration",
+                                        conf,
+                                        self.data.op_type.as_str()
+                                    ),
+                                ));
+                            }
+                        },
+                    }
                 }
             }
             OptPageMsg::DoneSaving(save, message) => {
@@ -591,7 +996,7 @@ impl SimpleComponent for OptPageModel {
                         //Save
                         self.set_conf(self.modifiedconf.clone());
                         let _ = sender.output(AppMsg::EditOpt(
-                            self.opt.join("."),
+                            optionpath::join(&self.opt),
                             self.modifiedconf.clone(),
                         ));
                         self.update_resettracker(|_| ()); // Simulate reset
@@ -602,20 +1007,145 @@ impl SimpleComponent for OptPageModel {
                             self.modifiedconf,
                             self.data.op_type.as_str()
                         );
-                        let _ = sender.output(AppMsg::SaveError(e));
+                        let _ = sender.output(AppMsg::SaveError(self.id, e));
                     }
                 } else {
                     //Error
-                    let _ = sender.output(AppMsg::SaveError(message));
+                    let _ = sender.output(AppMsg::SaveError(self.id, message));
                 }
 
                 self.set_saving(false);
                 let _ = sender.output(AppMsg::SetBusy(false));
             }
+            OptPageMsg::DoneEval(opt, value) => {
+                info!("OptPageMsg::DoneEval");
+                // Ignore results for options that are no longer shown
+                if opt == self.opt {
+                    self.set_evaluating(false);
+                    self.set_effective(Some(value));
+                }
+            }
+            OptPageMsg::SetConfig(config) => {
+                info!("OptPageMsg::SetConfig");
+                // Declarations are read from the nixpkgs the configuration uses
+                self.eval_handler
+                    .emit(EvalAsyncHandlerMsg::FindNixpkgs(config.clone()));
+                self.config = Some(config);
+            }
+            OptPageMsg::SetDb(db) => {
+                info!("OptPageMsg::SetDb");
+                self.db = db;
+            }
+            OptPageMsg::SetNixpkgs(path) => {
+                info!("OptPageMsg::SetNixpkgs");
+                self.set_nixpkgs(path);
+            }
+            OptPageMsg::SetNull(null) => {
+                info!("OptPageMsg::SetNull");
+                if null == self.isnull() {
+                    return;
+                }
+                let conf = if null {
+                    String::from("null")
+                } else if self.conf.trim() != "null" {
+                    self.conf.clone()
+                } else {
+                    String::new()
+                };
+                self.set_modifiedconf(conf);
+                self.update_valuetracker(|_| ()); // Simulate change to conf
+                self.update_resettracker(|_| ()); // Show the value in the simple editors
+            }
+            OptPageMsg::SetMultiline(multiline) => {
+                info!("OptPageMsg::SetMultiline");
+                if multiline == self.multiline {
+                    return;
+                }
+                if let Some(text) = nixstring::decode(&self.modifiedconf) {
+                    // A single line entry cannot hold the text
+                    if !multiline && text.contains('\n') {
+                        return;
+                    }
+                    let conf = if multiline {
+                        nixstring::encodeindented(&text)
+                    } else {
+                        nixstring::encode(&text)
+                    };
+                    if conf != self.modifiedconf {
+                        self.set_modifiedconf(conf);
+                        self.update_valuetracker(|_| ()); // Simulate change to conf
+                    }
+                }
+                self.set_multiline(multiline);
+            }
+            OptPageMsg::ShowDeclaration(decl) => {
+                info!("OptPageMsg::ShowDeclaration");
+                if let Some((path, line)) = self.declaration(&decl) {
+                    let _ = sender.output(AppMsg::ShowSource(path, line));
+                }
+            }
+            OptPageMsg::OpenDeclaration(decl) => {
+                info!("OptPageMsg::OpenDeclaration");
+                if let Some((path, line)) = self.declaration(&decl) {
+                    openexternal(&path, line);
+                }
+            }
             OptPageMsg::SetScheme(scheme) => {
                 info!("OptPageMsg::SetScheme");
                 self.set_scheme(sourceview5::StyleSchemeManager::default().scheme(&scheme));
             }
+            OptPageMsg::Edited(opt, conf) => {
+                info!("OptPageMsg::Edited");
+                // Only pick up the saved value if this page has no edits of its own
+                if opt == optionpath::join(&self.opt)
+                    && self.conf != conf
+                    && self.conf == self.modifiedconf
+                {
+                    self.set_multiline(nixstring::ismultiline(&conf));
+                    self.set_conf(conf.clone());
+                    self.set_modifiedconf(conf);
+                    self.update_valuetracker(|_| ());
+                    self.update_resettracker(|_| ());
+                }
+            }
+            OptPageMsg::EditedNote(opt, note) => {
+                info!("OptPageMsg::EditedNote");
+                if opt == optionpath::join(&self.opt)
+                    && self.note != note
+                    && self.note == self.modifiednote
+                {
+                    self.set_note(note.clone());
+                    self.set_modifiednote(note);
+                    self.update_resettracker(|_| ());
+                }
+            }
         }
     }
 }
+
+impl OptPageModel {
+    /// Whether the option type is `null or ...`.
+    fn nullable(&self) -> bool {
+        matches!(
+            OptionType::parse(self.data.op_type.as_str()),
+            OptionType::NullOr(_)
+        )
+    }
+
+    fn isnull(&self) -> bool {
+        self.modifiedconf.trim() == "null"
+    }
+
+    /// Resolves a declaration of the current option to a file and the line declaring it.
+    fn declaration(&self, decl: &str) -> Option<(PathBuf, usize)> {
+        let path = nixpkgs::resolve(self.nixpkgs.as_deref(), decl)?;
+        let line = match fs::read_to_string(&path) {
+            Ok(text) => nixpkgs::declarationline(&text, &self.refopt).unwrap_or(1),
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.to_string_lossy(), e);
+                return None;
+            }
+        };
+        Some((path, line))
+    }
+}
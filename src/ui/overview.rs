@@ -0,0 +1,352 @@
+use super::window::AppMsg;
+use crate::parse::config::exportmodule;
+use crate::parse::optionpath;
+use adw::prelude::*;
+use log::{info, warn};
+use relm4::*;
+use relm4_components::save_dialog::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+// Longest value shown in a row before it is cut off
+const MAXPREVIEW: usize = 60;
+
+#[tracker::track]
+pub struct OverviewModel {
+    /// Values saved in the configuration file
+    conf: HashMap<String, String>,
+    editedopts: HashMap<String, String>,
+    editednotes: HashMap<String, String>,
+    selected: HashSet<String>,
+    /// Changed whenever the rows need to be built again
+    rowtracker: u8,
+    #[tracker::no_eq]
+    save_dialog: Controller<SaveDialog>,
+}
+
+#[derive(Debug)]
+pub enum OverviewMsg {
+    Update(
+        HashMap<String, String>,
+        HashMap<String, String>,
+        HashMap<String, String>,
+    ),
+    Toggle(String, bool),
+    SelectAll(bool),
+    Reset,
+    Unset,
+    Export,
+    DoExport(PathBuf),
+    Open(String),
+    Ignore,
+}
+
+/// Whether an option has been saved to the configuration file or only in the editor.
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    Saved,
+    Pending,
+    Removed,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for OverviewModel {
+    type Init = gtk::Window;
+    type Input = OverviewMsg;
+    type Output = AppMsg;
+    type Widgets = OverviewWidgets;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            gtk::ActionBar {
+                pack_start = &gtk::CheckButton {
+                    set_label: Some("Select all"),
+                    #[watch]
+                    set_sensitive: !model.options().is_empty(),
+                    #[track(model.changed(OverviewModel::rowtracker()))]
+                    set_active: !model.selected.is_empty() && model.selected.len() == model.options().len(),
+                    connect_toggled[sender] => move |x| {
+                        sender.input(OverviewMsg::SelectAll(x.is_active()));
+                    },
+                },
+                pack_start = &gtk::Label {
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_label: &format!("{} selected", model.selected.len()),
+                },
+                pack_end = &gtk::Button {
+                    set_label: "Export",
+                    set_tooltip_text: Some("Write the selected options to a module file"),
+                    #[watch]
+                    set_sensitive: !model.selected.is_empty(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(OverviewMsg::Export);
+                    },
+                },
+                pack_end = &gtk::Button {
+                    set_label: "Unset",
+                    set_tooltip_text: Some("Remove the selected options from the configuration"),
+                    add_css_class: "destructive-action",
+                    #[watch]
+                    set_sensitive: model.selected.iter().any(|x| model.status(x) != Status::Removed),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(OverviewMsg::Unset);
+                    },
+                },
+                pack_end = &gtk::Button {
+                    set_label: "Reset",
+                    set_tooltip_text: Some("Discard unsaved changes to the selected options"),
+                    #[watch]
+                    set_sensitive: model.selected.iter().any(|x| model.status(x) != Status::Saved),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(OverviewMsg::Reset);
+                    },
+                },
+            },
+            #[name(view)]
+            gtk::Stack {
+                set_vexpand: true,
+                set_transition_type: gtk::StackTransitionType::Crossfade,
+                #[name(options)]
+                gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    adw::Clamp {
+                        #[name(groupbox)]
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_margin_all: 15,
+                            set_spacing: 24,
+                        }
+                    }
+                },
+                #[name(empty)]
+                adw::StatusPage {
+                    set_icon_name: Some("document-properties-symbolic"),
+                    set_title: "No configured options",
+                    set_description: Some("Options set in your configuration will be listed here"),
+                },
+            }
+        }
+    }
+
+    fn pre_view() {
+        if model.changed(OverviewModel::rowtracker()) {
+            while let Some(child) = groupbox.first_child() {
+                groupbox.remove(&child);
+            }
+            let mut groups: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+            for opt in model.options() {
+                let namespace = optionpath::split(opt)
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default();
+                groups.entry(namespace).or_default().push(opt);
+            }
+            for (namespace, opts) in groups {
+                let group = adw::PreferencesGroup::new();
+                group.set_title(&namespace);
+                let list = gtk::ListBox::new();
+                list.add_css_class("boxed-list");
+                list.set_selection_mode(gtk::SelectionMode::None);
+                for opt in opts {
+                    let row = adw::ActionRow::new();
+                    row.set_use_markup(false);
+                    row.set_title(opt);
+                    row.set_subtitle(&model.preview(opt));
+                    row.set_activatable(true);
+                    let check = gtk::CheckButton::new();
+                    check.set_valign(gtk::Align::Center);
+                    check.set_active(model.selected.contains(opt));
+                    let checksender = sender.clone();
+                    let o = opt.to_string();
+                    check.connect_toggled(move |x| {
+                        checksender.input(OverviewMsg::Toggle(o.clone(), x.is_active()));
+                    });
+                    row.add_prefix(&check);
+                    let status = gtk::Label::new(Some(match model.status(opt) {
+                        Status::Saved => "Saved",
+                        Status::Pending => "Pending",
+                        Status::Removed => "Pending removal",
+                    }));
+                    status.set_valign(gtk::Align::Center);
+                    status.add_css_class(match model.status(opt) {
+                        Status::Saved => "dim-label",
+                        Status::Pending => "accent",
+                        Status::Removed => "error",
+                    });
+                    row.add_suffix(&status);
+                    let opensender = sender.clone();
+                    let o = opt.to_string();
+                    row.connect_activated(move |_| {
+                        opensender.input(OverviewMsg::Open(o.clone()));
+                    });
+                    list.append(&row);
+                }
+                group.add(&list);
+                groupbox.append(&group);
+            }
+            if model.options().is_empty() {
+                view.set_visible_child(empty);
+            } else {
+                view.set_visible_child(options);
+            }
+        }
+    }
+
+    fn init(
+        parent_window: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let save_dialog = SaveDialog::builder()
+            .transient_for_native(&parent_window)
+            .launch(SaveDialogSettings::default())
+            .forward(sender.input_sender(), |response| match response {
+                SaveDialogResponse::Accept(path) => OverviewMsg::DoExport(path),
+                SaveDialogResponse::Cancel => OverviewMsg::Ignore,
+            });
+        let model = OverviewModel {
+            conf: HashMap::new(),
+            editedopts: HashMap::new(),
+            editednotes: HashMap::new(),
+            selected: HashSet::new(),
+            rowtracker: 0,
+            save_dialog,
+            tracker: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset();
+        match msg {
+            OverviewMsg::Update(conf, editedopts, editednotes) => {
+                info!("OverviewMsg::Update");
+                self.conf = conf;
+                self.editedopts = editedopts;
+                self.editednotes = editednotes;
+                // Drop the selection of options that are gone
+                let options = self.options().into_iter().cloned().collect::<HashSet<_>>();
+                self.selected.retain(|x| options.contains(x));
+                self.update_rowtracker(|_| ());
+            }
+            OverviewMsg::Toggle(opt, active) => {
+                if active {
+                    self.update_selected(|x| {
+                        x.insert(opt);
+                    });
+                } else {
+                    self.update_selected(|x| {
+                        x.remove(&opt);
+                    });
+                }
+            }
+            OverviewMsg::SelectAll(all) => {
+                info!("OverviewMsg::SelectAll");
+                // The checkbox is also toggled when rows are rebuilt
+                let options = self.options();
+                if all && self.selected.len() != options.len() {
+                    let options = options.into_iter().cloned().collect();
+                    self.set_selected(options);
+                    self.update_rowtracker(|_| ());
+                } else if !all && self.selected.len() == options.len() {
+                    self.update_selected(|x| x.clear());
+                    self.update_rowtracker(|_| ());
+                }
+            }
+            OverviewMsg::Reset => {
+                info!("OverviewMsg::Reset");
+                let selected = self.selected.iter().cloned().collect::<Vec<_>>();
+                let _ = sender.output(AppMsg::ResetOpts(selected));
+            }
+            OverviewMsg::Unset => {
+                info!("OverviewMsg::Unset");
+                for opt in &self.selected {
+                    let _ = sender.output(AppMsg::EditOpt(opt.to_string(), String::new()));
+                }
+            }
+            OverviewMsg::Export => {
+                info!("OverviewMsg::Export");
+                self.save_dialog
+                    .emit(SaveDialogMsg::SaveAs(String::from("options.nix")));
+            }
+            OverviewMsg::DoExport(path) => {
+                info!("OverviewMsg::DoExport");
+                let mut opts = self
+                    .selected
+                    .iter()
+                    .filter_map(|x| match self.value(x) {
+                        v if v.is_empty() => None,
+                        v => Some((x.to_string(), v.to_string())),
+                    })
+                    .collect::<Vec<_>>();
+                opts.sort();
+                if let Err(e) = fs::write(&path, exportmodule(&opts)) {
+                    warn!("Failed to export to {}: {}", path.to_string_lossy(), e);
+                }
+            }
+            OverviewMsg::Open(opt) => {
+                info!("OverviewMsg::Open");
+                let _ = sender.output(AppMsg::OpenConfigured(opt));
+            }
+            OverviewMsg::Ignore => {}
+        }
+    }
+}
+
+impl OverviewModel {
+    /// All options that are set in the configuration or edited, sorted by name.
+    fn options(&self) -> Vec<&String> {
+        let mut out = self
+            .conf
+            .keys()
+            .chain(self.editedopts.keys())
+            .chain(
+                self.editednotes
+                    .keys()
+                    .filter(|x| self.conf.contains_key(*x)),
+            )
+            .collect::<Vec<_>>();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn status(&self, opt: &str) -> Status {
+        match self.editedopts.get(opt) {
+            Some(x) if x.is_empty() => Status::Removed,
+            Some(_) => Status::Pending,
+            _ if self.editednotes.contains_key(opt) => Status::Pending,
+            _ => Status::Saved,
+        }
+    }
+
+    /// Value the option will have once the configuration is saved.
+    fn value(&self, opt: &str) -> &str {
+        self.editedopts
+            .get(opt)
+            .or_else(|| self.conf.get(opt))
+            .map(|x| x.as_str())
+            .unwrap_or_default()
+    }
+
+    fn preview(&self, opt: &str) -> String {
+        let value = self.value(opt).trim();
+        if value.is_empty() {
+            return String::from("unset");
+        }
+        let line = value.lines().next().unwrap_or_default();
+        if line.chars().count() > MAXPREVIEW || line.len() < value.len() {
+            format!("{}…", line.chars().take(MAXPREVIEW).collect::<String>())
+        } else {
+            line.to_string()
+        }
+    }
+}
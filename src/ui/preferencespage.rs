@@ -1,12 +1,13 @@
 use super::window::AppMsg;
+use crate::parse::preferences::{extraoptions, setextraoptions};
 use adw::prelude::*;
+use log::warn;
 use nix_data::config::configfile::NixDataConfig;
-use relm4::*;
+use relm4::{factory::*, *};
 use relm4_components::open_dialog::*;
 use std::path::PathBuf;
 
 #[tracker::track]
-#[derive(Debug)]
 pub struct PreferencesPageModel {
     prefwindow: adw::PreferencesWindow,
     configpath: PathBuf,
@@ -16,10 +17,19 @@ pub struct PreferencesPageModel {
     flakearg: Option<String>,
     origflakearg: Option<String>,
     generations: Option<u32>,
+    /// Options files and directories of private modules
+    extraoptions: Vec<String>,
+    origextraoptions: Vec<String>,
+    #[tracker::no_eq]
+    extrarows: FactoryVecDeque<ExtraOptionsRow>,
     #[tracker::no_eq]
     open_dialog: Controller<OpenDialog>,
     #[tracker::no_eq]
     flake_file_dialog: Controller<OpenDialog>,
+    #[tracker::no_eq]
+    extra_file_dialog: Controller<OpenDialog>,
+    #[tracker::no_eq]
+    extra_folder_dialog: Controller<OpenDialog>,
     error: bool,
 }
 
@@ -32,6 +42,10 @@ pub enum PreferencesPageMsg {
     SetConfigPath(PathBuf),
     SetFlakePath(Option<PathBuf>),
     SetFlakeArg(Option<String>),
+    OpenExtraFile,
+    OpenExtraFolder,
+    AddExtraOptions(PathBuf),
+    RemoveExtraOptions(String),
     Close,
     Ignore,
 }
@@ -160,6 +174,37 @@ impl SimpleComponent for PreferencesPageModel {
                         set_text: model.flakearg.as_ref().unwrap_or(&String::new())
                     }
 
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: "Additional options",
+                    set_description: Some("Options files of modules outside NixOS, such as an options.json built from a private module set"),
+                    #[wrap(Some)]
+                    set_header_suffix = &gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_valign: gtk::Align::Center,
+                        add_css_class: "linked",
+                        gtk::Button {
+                            set_icon_name: "document-open-symbolic",
+                            set_tooltip_text: Some("Add options file"),
+                            connect_clicked[sender] => move |_| {
+                                sender.input(PreferencesPageMsg::OpenExtraFile);
+                            }
+                        },
+                        gtk::Button {
+                            set_icon_name: "folder-open-symbolic",
+                            set_tooltip_text: Some("Add folder of options files"),
+                            connect_clicked[sender] => move |_| {
+                                sender.input(PreferencesPageMsg::OpenExtraFolder);
+                            }
+                        },
+                    },
+                    #[local_ref]
+                    add = extralistbox -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                        #[watch]
+                        set_visible: !model.extraoptions.is_empty(),
+                    }
                 }
             }
         }
@@ -184,6 +229,29 @@ impl SimpleComponent for PreferencesPageModel {
                 OpenDialogResponse::Accept(path) => PreferencesPageMsg::SetFlakePath(Some(path)),
                 OpenDialogResponse::Cancel => PreferencesPageMsg::Ignore,
             });
+        let jsonfilter = gtk::FileFilter::new();
+        jsonfilter.set_name(Some("Options files"));
+        jsonfilter.add_pattern("*.json");
+        let extra_file_dialog = OpenDialog::builder()
+            .transient_for_native(root)
+            .launch(OpenDialogSettings {
+                filters: vec![jsonfilter],
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |response| match response {
+                OpenDialogResponse::Accept(path) => PreferencesPageMsg::AddExtraOptions(path),
+                OpenDialogResponse::Cancel => PreferencesPageMsg::Ignore,
+            });
+        let extra_folder_dialog = OpenDialog::builder()
+            .transient_for_native(root)
+            .launch(OpenDialogSettings {
+                folder_mode: true,
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |response| match response {
+                OpenDialogResponse::Accept(path) => PreferencesPageMsg::AddExtraOptions(path),
+                OpenDialogResponse::Cancel => PreferencesPageMsg::Ignore,
+            });
         let model = PreferencesPageModel {
             prefwindow: root.clone(),
             configpath: PathBuf::new(),
@@ -193,12 +261,19 @@ impl SimpleComponent for PreferencesPageModel {
             flakearg: None,
             origflakearg: None,
             generations: None,
+            extraoptions: vec![],
+            origextraoptions: vec![],
+            extrarows: FactoryVecDeque::new(gtk::ListBox::new(), sender.input_sender()),
             open_dialog,
             flake_file_dialog,
+            extra_file_dialog,
+            extra_folder_dialog,
             error: false,
             tracker: 0,
         };
 
+        let extralistbox = model.extrarows.widget();
+
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -217,6 +292,9 @@ impl SimpleComponent for PreferencesPageModel {
                 self.set_flakearg(config.flakearg);
                 self.origflakearg = self.flakearg.clone();
                 self.generations = config.generations;
+                self.set_extraoptions(extraoptions());
+                self.origextraoptions = self.extraoptions.clone();
+                self.fillextrarows();
                 self.prefwindow.show();
                 self.error = false;
             }
@@ -229,6 +307,9 @@ impl SimpleComponent for PreferencesPageModel {
                 self.origflake = self.flake.clone();
                 self.set_flakearg(config.flakearg);
                 self.origflakearg = self.flakearg.clone();
+                self.set_extraoptions(extraoptions());
+                self.origextraoptions = self.extraoptions.clone();
+                self.fillextrarows();
                 self.prefwindow.present();
                 self.error = true;
             }
@@ -243,7 +324,28 @@ impl SimpleComponent for PreferencesPageModel {
             PreferencesPageMsg::SetFlakeArg(arg) => {
                 self.flakearg = arg;
             }
+            PreferencesPageMsg::OpenExtraFile => self.extra_file_dialog.emit(OpenDialogMsg::Open),
+            PreferencesPageMsg::OpenExtraFolder => {
+                self.extra_folder_dialog.emit(OpenDialogMsg::Open)
+            }
+            PreferencesPageMsg::AddExtraOptions(path) => {
+                let path = path.to_string_lossy().to_string();
+                if !self.extraoptions.contains(&path) {
+                    self.update_extraoptions(|x| x.push(path));
+                    self.fillextrarows();
+                }
+            }
+            PreferencesPageMsg::RemoveExtraOptions(path) => {
+                self.update_extraoptions(|x| x.retain(|x| x != &path));
+                self.fillextrarows();
+            }
             PreferencesPageMsg::Close => {
+                let extrachanged = !self.extraoptions.eq(&self.origextraoptions);
+                if extrachanged {
+                    if let Err(e) = setextraoptions(&self.extraoptions) {
+                        warn!("Failed to save additional options files: {}", e);
+                    }
+                }
                 if !self.configpath.eq(&self.origconfigpath)
                     || !self.flake.eq(&self.origflake)
                     || !self.flakearg.eq(&self.origflakearg)
@@ -255,6 +357,9 @@ impl SimpleComponent for PreferencesPageModel {
                         flakearg: self.flakearg.clone(),
                         generations: self.generations,
                     }));
+                } else if extrachanged {
+                    // A changed configuration reloads the options anyway
+                    let _ = sender.output(AppMsg::TryLoad);
                 }
                 self.prefwindow.hide();
             }
@@ -262,3 +367,59 @@ impl SimpleComponent for PreferencesPageModel {
         }
     }
 }
+
+impl PreferencesPageModel {
+    fn fillextrarows(&mut self) {
+        let mut extrarows_guard = self.extrarows.guard();
+        extrarows_guard.clear();
+        for path in &self.extraoptions {
+            extrarows_guard.push_back(path.to_string());
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ExtraOptionsRow {
+    path: String,
+}
+
+#[derive(Debug)]
+enum ExtraOptionsRowOutput {
+    Remove(String),
+}
+
+#[relm4::factory]
+impl FactoryComponent for ExtraOptionsRow {
+    type Init = String;
+    type Input = ();
+    type Output = ExtraOptionsRowOutput;
+    type Widgets = ExtraOptionsRowWidgets;
+    type ParentWidget = gtk::ListBox;
+    type ParentInput = PreferencesPageMsg;
+    type CommandOutput = ();
+
+    view! {
+        adw::ActionRow {
+            set_title: &gtk::glib::markup_escape_text(&self.path),
+            add_suffix = &gtk::Button {
+                set_icon_name: "user-trash-symbolic",
+                set_tooltip_text: Some("Remove"),
+                set_valign: gtk::Align::Center,
+                add_css_class: "flat",
+                connect_clicked[sender, path = self.path.clone()] => move |_| {
+                    sender.output(ExtraOptionsRowOutput::Remove(path.to_string()));
+                }
+            },
+        }
+    }
+
+    fn init_model(path: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        Self { path }
+    }
+
+    fn output_to_parent_input(output: Self::Output) -> Option<PreferencesPageMsg> {
+        Some(match output {
+            ExtraOptionsRowOutput::Remove(x) => PreferencesPageMsg::RemoveExtraOptions(x),
+        })
+    }
+}
@@ -1,8 +1,11 @@
+use super::configcheck::{ConfigCheckHandler, ConfigCheckHandlerMsg};
 use super::window::AppMsg;
 use crate::config::LIBEXECDIR;
 use adw::prelude::*;
 use gtk::{gio, glib};
+use nix_data::config::configfile::NixDataConfig;
 use relm4::*;
+use std::convert::identity;
 use std::io::Write;
 use std::process::Command;
 use std::process::*;
@@ -17,6 +20,8 @@ pub struct RebuildModel {
     flake: Option<String>,
     scheme: Option<sourceview5::StyleScheme>,
     terminal: vte::Terminal,
+    #[tracker::no_eq]
+    checker: WorkerController<ConfigCheckHandler>,
 }
 
 #[derive(Debug)]
@@ -24,6 +29,10 @@ pub enum RebuildMsg {
     Rebuild(String, String, Option<String>),
     FinishSuccess,
     FinishError(Option<String>),
+    CheckFailed(String),
+    Check(String, NixDataConfig),
+    DoneCheck(Result<String, String>),
+    RebuildAnyways,
     WriteConfig(String, String, bool),
     KeepEditing,
     Reset,
@@ -39,6 +48,13 @@ enum RebuildStatus {
     Building,
     Success,
     Error,
+    /// Type checking the edited options failed before anything was built
+    Invalid,
+    /// Evaluating the whole configuration without writing it
+    Checking,
+    Valid,
+    /// The whole configuration failed to evaluate
+    CheckError,
 }
 
 #[relm4::component(pub)]
@@ -116,6 +132,73 @@ impl SimpleComponent for RebuildModel {
                             set_label: "Rebuild failed! See below for error message.",
                             add_css_class: "dim-label",
                         }
+                    },
+                    #[name(checking)]
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        gtk::Spinner {
+                            set_spinning: true,
+                            set_height_request: 60,
+                        },
+                        gtk::Label {
+                            set_label: "Checking...",
+                            add_css_class: "title-1",
+                        },
+                    },
+                    #[name(valid)]
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        gtk::Image {
+                            add_css_class: "success",
+                            set_icon_name: Some("object-select-symbolic"),
+                            set_pixel_size: 128,
+                        },
+                        gtk::Label {
+                            set_label: "Valid configuration",
+                            add_css_class: "title-1",
+                        },
+                        gtk::Label {
+                            set_label: "The configuration evaluates. Nothing has been changed yet.",
+                            add_css_class: "dim-label",
+                        }
+                    },
+                    #[name(checkerror)]
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        gtk::Image {
+                            add_css_class: "error",
+                            set_icon_name: Some("dialog-error-symbolic"),
+                            set_pixel_size: 128,
+                        },
+                        gtk::Label {
+                            set_label: "Invalid configuration",
+                            add_css_class: "title-1",
+                        },
+                        gtk::Label {
+                            set_label: "The configuration does not evaluate. Nothing has been changed yet.",
+                            add_css_class: "dim-label",
+                        }
+                    },
+                    #[name(invalid)]
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 10,
+                        gtk::Image {
+                            add_css_class: "warning",
+                            set_icon_name: Some("dialog-warning-symbolic"),
+                            set_pixel_size: 128,
+                        },
+                        gtk::Label {
+                            set_label: "Invalid configuration",
+                            add_css_class: "title-1",
+                        },
+                        gtk::Label {
+                            set_label: "Some options do not match their types. Nothing has been changed yet.",
+                            add_css_class: "dim-label",
+                        }
                     }
                 },
                 gtk::Frame {
@@ -144,7 +227,7 @@ impl SimpleComponent for RebuildModel {
                     set_orientation: gtk::Orientation::Horizontal,
                     set_homogeneous: true,
                     #[track(model.changed(RebuildModel::status()))]
-                    set_visible: model.status != RebuildStatus::Building,
+                    set_visible: model.status != RebuildStatus::Building && model.status != RebuildStatus::Checking,
                     gtk::Button {
                         set_label: "Close",
                         #[track(model.changed(RebuildModel::status()))]
@@ -153,6 +236,14 @@ impl SimpleComponent for RebuildModel {
                             sender.input(RebuildMsg::Save)
                         }
                     },
+                    gtk::Button {
+                        set_label: "Close",
+                        #[track(model.changed(RebuildModel::status()))]
+                        set_visible: model.status == RebuildStatus::Valid || model.status == RebuildStatus::CheckError,
+                        connect_clicked[sender] => move |_| {
+                            sender.input(RebuildMsg::Close)
+                        }
+                    },
                     gtk::Button {
                         add_css_class: "destructive-action",
                         set_label: "Save Anyways",
@@ -170,10 +261,19 @@ impl SimpleComponent for RebuildModel {
                             sender.input(RebuildMsg::Reset)
                         }
                     },
+                    gtk::Button {
+                        add_css_class: "destructive-action",
+                        set_label: "Rebuild Anyways",
+                        #[track(model.changed(RebuildModel::status()))]
+                        set_visible: model.status == RebuildStatus::Invalid,
+                        connect_clicked[sender] => move |_| {
+                            sender.input(RebuildMsg::RebuildAnyways)
+                        }
+                    },
                     gtk::Button {
                         set_label: "Keep Editing",
                         #[track(model.changed(RebuildModel::status()))]
-                        set_visible: model.status == RebuildStatus::Error,
+                        set_visible: model.status == RebuildStatus::Error || model.status == RebuildStatus::Invalid,
                         connect_clicked[sender] => move |_| {
                             sender.input(RebuildMsg::KeepEditing)
                         }
@@ -190,6 +290,10 @@ impl SimpleComponent for RebuildModel {
             }
             RebuildStatus::Success => statusstack.set_visible_child(success),
             RebuildStatus::Error => statusstack.set_visible_child(error),
+            RebuildStatus::Invalid => statusstack.set_visible_child(invalid),
+            RebuildStatus::Checking => statusstack.set_visible_child(checking),
+            RebuildStatus::Valid => statusstack.set_visible_child(valid),
+            RebuildStatus::CheckError => statusstack.set_visible_child(checkerror),
         }
     }
 
@@ -198,6 +302,9 @@ impl SimpleComponent for RebuildModel {
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let checker = ConfigCheckHandler::builder()
+            .detach_worker(())
+            .forward(sender.input_sender(), identity);
         let model = RebuildModel {
             hidden: true,
             status: RebuildStatus::Building,
@@ -206,6 +313,7 @@ impl SimpleComponent for RebuildModel {
             flake: None,
             scheme: None,
             terminal: vte::Terminal::new(),
+            checker,
             tracker: 0,
         };
 
@@ -279,10 +387,18 @@ impl SimpleComponent for RebuildModel {
             RebuildMsg::FinishSuccess => {
                 self.set_status(RebuildStatus::Success);
             }
-            RebuildMsg::FinishError(_msg) => {
+            RebuildMsg::FinishError(msg) => {
+                if let Some(msg) = msg {
+                    self.terminal
+                        .feed(format!("{}\r\n", msg.replace('\n', "\r\n")).as_bytes());
+                }
                 self.update_hidden(|x| *x = false);
                 self.set_status(RebuildStatus::Error);
             }
+            RebuildMsg::KeepEditing if self.status == RebuildStatus::Invalid => {
+                // Nothing was written yet
+                sender.input(RebuildMsg::Close);
+            }
             RebuildMsg::KeepEditing => {
                 sender.input(RebuildMsg::WriteConfig(
                     self.config.to_string(),
@@ -291,6 +407,33 @@ impl SimpleComponent for RebuildModel {
                 ));
                 sender.input(RebuildMsg::Close);
             }
+            RebuildMsg::CheckFailed(msg) => {
+                self.terminal
+                    .feed(format!("{}\r\n", msg.replace('\n', "\r\n")).as_bytes());
+                self.update_hidden(|x| *x = false);
+                self.set_status(RebuildStatus::Invalid);
+            }
+            RebuildMsg::Check(f, config) => {
+                self.update_hidden(|x| *x = false);
+                self.set_status(RebuildStatus::Checking);
+                self.checker.emit(ConfigCheckHandlerMsg::Check(f, config));
+            }
+            RebuildMsg::DoneCheck(result) => {
+                let (msg, status) = match result {
+                    Ok(drv) => (format!("Evaluated {}", drv), RebuildStatus::Valid),
+                    Err(e) => (e, RebuildStatus::CheckError),
+                };
+                self.terminal
+                    .feed(format!("{}\r\n", msg.replace('\n', "\r\n")).as_bytes());
+                self.set_status(status);
+            }
+            RebuildMsg::RebuildAnyways => {
+                // Clear the type errors without spawning anything that could
+                // finish after the rebuild has started
+                self.terminal.reset(true, true);
+                self.terminal.feed(b"\x1b[2J\x1b[H");
+                let _ = sender.output(AppMsg::StartRebuild);
+            }
             RebuildMsg::Reset => {
                 let _ = sender.output(AppMsg::ResetConfig);
                 sender.input(RebuildMsg::Close);
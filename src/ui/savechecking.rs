@@ -1,16 +1,24 @@
+use super::effectivevalue::{flakedir, flakehost};
 use super::window::AppMsg;
+use crate::parse::evaluator::{self, EvalError};
+use crate::parse::nixpkgs::{nixpkgspath, CHANNELPATH};
+use crate::parse::optiondb::OptionDb;
+use crate::parse::optionpath::{join, split};
+use crate::parse::options::innerpath;
+use crate::parse::validate::{validate, Validation};
 use crate::ui::optionpage::OptPageMsg;
 use adw::prelude::*;
-use log::{debug, info};
+use log::{debug, info, warn};
+use nix_data::config::configfile::NixDataConfig;
 use relm4::*;
 use sourceview5::prelude::*;
-use std::{path::Path, process::Command};
+use std::{path::Path, process::Command, sync::Arc};
 
 pub struct SaveAsyncHandler;
 
 #[derive(Debug)]
 pub enum SaveAsyncHandlerMsg {
-    SaveCheck(String, String, String, Vec<String>),
+    SaveCheck(String, String, String, Arc<OptionDb>, NixDataConfig),
 }
 
 impl Worker for SaveAsyncHandler {
@@ -24,79 +32,13 @@ impl Worker for SaveAsyncHandler {
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
-            SaveAsyncHandlerMsg::SaveCheck(opt, refopt, conf, alloptions) => {
+            SaveAsyncHandlerMsg::SaveCheck(opt, refopt, conf, db, config) => {
                 info!("Received SaveCheck message");
                 debug!("opt: {}\nrefopt: {}", opt, refopt);
-                // For users.users.<name>.autoSubUidGidRange
-                // (options.users.users.type.getSubOptions []).autoSubUidGidRange.type.check
-                let checkcmd = {
-                    let p = refopt.split('.').collect::<Vec<_>>();
-                    let mut r: Vec<Vec<String>> = vec![vec![]];
-                    let mut indexvec: Vec<usize> = vec![];
-                    let mut j = 0;
-                    for i in 0..p.len() {
-                        if p[i] == "*" || p[i] == "<name>" {
-                            r.push(vec![]);
-                            if let Ok(x) = opt.split('.').collect::<Vec<_>>()[i].parse::<usize>() {
-                                indexvec.push(x);
-                            }
-                            j += 1;
-                        } else if alloptions.contains(&p[..i].join(".")) && i + 1 < p.len()
-                        /* Check if option exists */
-                        {
-                            r.push(vec![]);
-                            j += 1;
-                            r[j].push(p[i].to_string());
-                        } else {
-                            r[j].push(p[i].to_string());
-                        }
-                    }
-                    let mut s = format!("options.{}", r[0].join("."));
-                    for y in r[1..].iter() {
-                        s = format!("({}.type.getSubOptions []).{}", s, y.join("."));
-                    }
-                    format!("{}.type.check", s)
-                };
-                let output =
-                    if Path::new("/nix/var/nix/profiles/per-user/root/channels/nixos").exists() {
-                        Command::new("nix-instantiate")
-                            .arg("--eval")
-                            .arg("--expr")
-                            .arg(format!(
-                                "with import <nixpkgs/nixos> {{}}; {} ({})",
-                                checkcmd, conf
-                            ))
-                            .output()
-                    } else {
-                        match Command::new("nix").arg("eval").arg("nixpkgs#path").output() {
-                            Ok(nixpath) => {
-                                let nixospath = format!(
-                                    "{}/nixos/lib/eval-config.nix",
-                                    String::from_utf8_lossy(&nixpath.stdout).trim()
-                                );
-                                Command::new("nix-instantiate")
-                                    .arg("--eval")
-                                    .arg("--expr")
-                                    .arg(format!(
-                                        "with import {} {{ modules = []; }}; {} ({})",
-                                        nixospath, checkcmd, conf
-                                    ))
-                                    .output()
-                            }
-                            Err(e) => Err(e),
-                        }
-                    };
-                let (b, s) = match output {
-                    Ok(output) => {
-                        if output.status.success() {
-                            let output = String::from_utf8(output.stdout).unwrap();
-                            (true, output)
-                        } else {
-                            let output = String::from_utf8(output.stderr).unwrap();
-                            (false, output)
-                        }
-                    }
-                    Err(e) => (false, e.to_string()),
+                let checkcmd = checkexpr(&opt, &refopt, &db);
+                let (b, s) = match evaljson(&format!("{} ({})", checkcmd, conf), &config) {
+                    Ok(output) => (true, format!("{}\n", output)),
+                    Err(e) => (false, e),
                 };
                 let _ = sender.output(OptPageMsg::DoneSaving(b, s));
             }
@@ -104,6 +46,203 @@ impl Worker for SaveAsyncHandler {
     }
 }
 
+/// An edited option to type check along with others.
+#[derive(Debug, Clone)]
+pub struct CheckEntry {
+    pub opt: String,
+    pub refopt: String,
+    pub value: String,
+    pub op_type: String,
+}
+
+pub struct BatchCheckHandler;
+
+#[derive(Debug)]
+pub enum BatchCheckHandlerMsg {
+    Check(Vec<CheckEntry>, Arc<OptionDb>, NixDataConfig),
+}
+
+impl Worker for BatchCheckHandler {
+    type Init = ();
+    type Input = BatchCheckHandlerMsg;
+    type Output = AppMsg;
+
+    fn init(_params: Self::Init, _sender: relm4::ComponentSender<Self>) -> Self {
+        Self
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            BatchCheckHandlerMsg::Check(entries, db, config) => {
+                info!("Received Check message");
+                let _ = sender.output(AppMsg::DoneBatchCheck(batchcheck(&entries, &db, &config)));
+            }
+        }
+    }
+}
+
+/// Type checks all entries in a single evaluation of nixpkgs and returns the
+/// options that failed along with the reason.
+pub fn batchcheck(
+    entries: &[CheckEntry],
+    db: &OptionDb,
+    config: &NixDataConfig,
+) -> Vec<(String, String)> {
+    let mut failed = vec![];
+    let mut checked = vec![];
+    for entry in entries {
+        // A value that does not parse would break the whole expression, and
+        // values of simple types need no evaluation at all
+        match validate(&entry.value, &entry.op_type) {
+            Validation::Valid => {}
+            Validation::Invalid(e) => failed.push((entry.opt.to_string(), e)),
+            // Options from additional files are not declared in the nixpkgs evaluated here
+            Validation::Unknown if db.source(&entry.refopt).is_some() => {}
+            Validation::Unknown => checked.push(entry),
+        }
+    }
+    if checked.is_empty() {
+        return failed;
+    }
+    let expr = format!(
+        "[ {} ]",
+        checked
+            .iter()
+            .map(|x| format!(
+                "(let r = builtins.tryEval ({} ({})); in r.success && r.value)",
+                checkexpr(&x.opt, &x.refopt, db),
+                x.value
+            ))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let results = evaljson(&expr, config)
+        .and_then(|x| serde_json::from_str::<Vec<bool>>(&x).map_err(|e| e.to_string()));
+    match results {
+        Ok(results) if results.len() == checked.len() => {
+            for (entry, ok) in checked.into_iter().zip(results) {
+                if !ok {
+                    failed.push((
+                        entry.opt.to_string(),
+                        format!("{} is not of type {}", entry.value, entry.op_type),
+                    ));
+                }
+            }
+        }
+        results => {
+            // Errors such as missing attributes cannot be caught inside the
+            // expression, so find the culprits one option at a time
+            if let Err(e) = results {
+                warn!("Batch type check failed: {}", e);
+            }
+            for entry in checked {
+                let output = evaljson(
+                    &format!(
+                        "{} ({})",
+                        checkexpr(&entry.opt, &entry.refopt, db),
+                        entry.value
+                    ),
+                    config,
+                );
+                match output {
+                    Ok(output) if output == "true" => {}
+                    Ok(_) => failed.push((
+                        entry.opt.to_string(),
+                        format!("{} is not of type {}", entry.value, entry.op_type),
+                    )),
+                    Err(e) => failed.push((entry.opt.to_string(), e)),
+                }
+            }
+        }
+    }
+    failed
+}
+
+/// Builds the expression returning the type check function of an option.
+fn checkexpr(opt: &str, refopt: &str, db: &OptionDb) -> String {
+    // For users.users.<name>.autoSubUidGidRange
+    // (options.users.users.type.getSubOptions []).autoSubUidGidRange.type.check
+    let p = split(refopt);
+    let o = split(opt);
+    // Nested configurations such as containers are checked against the top level options
+    let nested = p.len() - innerpath(&p).len();
+    let p = &p[nested..];
+    let o = o.get(nested..).unwrap_or_default();
+    let mut r: Vec<Vec<String>> = vec![vec![]];
+    let mut indexvec: Vec<usize> = vec![];
+    let mut j = 0;
+    for i in 0..p.len() {
+        if p[i] == "*" || p[i] == "<name>" {
+            r.push(vec![]);
+            if let Some(Ok(x)) = o.get(i).map(|x| x.parse::<usize>()) {
+                indexvec.push(x);
+            }
+            j += 1;
+        } else if db.contains(&join(&p[..i])) && i + 1 < p.len()
+        /* Check if option exists */
+        {
+            r.push(vec![]);
+            j += 1;
+            r[j].push(p[i].to_string());
+        } else {
+            r[j].push(p[i].to_string());
+        }
+    }
+    let mut s = format!("options.{}", join(&r[0]));
+    for y in r[1..].iter() {
+        s = format!("({}.type.getSubOptions []).{}", s, join(y));
+    }
+    format!("{}.type.check", s)
+}
+
+/// Expression evaluating to the NixOS module system option types are taken from.
+///
+/// Flakes use the nixpkgs locked in their `flake.lock`, which may be a
+/// different release than the channel or the registry.
+pub fn nixoscontext(config: &NixDataConfig) -> Option<String> {
+    if config.flake.is_none() && Path::new(CHANNELPATH).exists() {
+        Some(String::from("import <nixpkgs/nixos> { }"))
+    } else {
+        let host = flakehost(config);
+        let nixpath = nixpkgspath(
+            config
+                .flake
+                .as_deref()
+                .map(|x| (flakedir(x), host.as_str())),
+        )?;
+        Some(format!(
+            "import {}/nixos/lib/eval-config.nix {{ modules = [ ]; }}",
+            nixpath.to_string_lossy()
+        ))
+    }
+}
+
+/// Evaluates `body` to JSON with the NixOS options in scope, through the shared
+/// evaluator if it is running.
+fn evaljson(body: &str, config: &NixDataConfig) -> Result<String, String> {
+    let context = nixoscontext(config).ok_or_else(|| String::from("Could not find nixpkgs"))?;
+    match evaluator::eval(&context, &format!("with sys; {}", body)) {
+        Ok(x) => return Ok(x),
+        Err(EvalError::Eval(e)) => return Err(e),
+        Err(EvalError::Unavailable(e)) => {
+            warn!("Evaluator unavailable, running nix-instantiate: {}", e)
+        }
+    }
+    let output = Command::new("nix-instantiate")
+        .arg("--eval")
+        .arg("--strict")
+        .arg("--json")
+        .arg("--expr")
+        .arg(format!("with ({}); {}", context, body))
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
 pub struct SaveErrorModel {
     hidden: bool,
     msg: String,
@@ -1,4 +1,5 @@
 use super::window::*;
+use crate::parse::optionpath;
 use adw::prelude::*;
 use relm4::{factory::*, *};
 use std::collections::HashMap;
@@ -72,9 +73,9 @@ impl SimpleComponent for SearchEntryModel {
                                 add_css_class: "boxed-list",
                                 append = &adw::ActionRow {
                                     #[watch]
-                                    set_title: &model.customopt.join("."),
+                                    set_title: &gtk::glib::markup_escape_text(&optionpath::join(&model.customopt)),
                                     #[watch]
-                                    set_sensitive: !model.customopt.contains(&String::from("&lt;name&gt;")),
+                                    set_sensitive: !model.customopt.contains(&String::from("<name>")),
                                     set_selectable: false,
                                     set_activatable: true,
                                     add_suffix = &gtk::Image {
@@ -125,17 +126,22 @@ impl SimpleComponent for SearchEntryModel {
                 }
                 for i in 0..pos.len() {
                     if pos[i] == "<name>" {
-                        let mut op = pos.to_vec();
-                        op[i] = "<b>&lt;name&gt;</b>".to_string();
-                        nameopts_guard
-                            .push_back((op.join(".").replace("<name>", "&lt;name&gt;"), i));
+                        let op = pos
+                            .iter()
+                            .enumerate()
+                            .map(|(j, x)| {
+                                if i == j {
+                                    String::from("<b>&lt;name&gt;</b>")
+                                } else {
+                                    gtk::glib::markup_escape_text(&optionpath::quote(x)).to_string()
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        nameopts_guard.push_back((op.join("."), i));
                     }
                 }
                 self.position = pos.clone();
-                self.customopt = pos
-                    .iter()
-                    .map(|x| x.replace("<name>", "&lt;name&gt;"))
-                    .collect();
+                self.customopt = pos;
                 self.hidden = false;
             }
             SearchEntryMsg::Close => {
@@ -159,7 +165,7 @@ impl SimpleComponent for SearchEntryModel {
                             }
                             if !existing.contains(&self.customopt[i]) {
                                 let _ = sender.output(AppMsg::AddNameAttr(
-                                    Some(self.customopt[..i].join(".")),
+                                    Some(optionpath::join(&self.customopt[..i])),
                                     self.customopt[i].clone(),
                                 ));
                             }
@@ -184,7 +190,8 @@ impl SimpleComponent for SearchEntryModel {
                                 0
                             };
                             n.insert(i, num);
-                            let _ = sender.output(AppMsg::AddStar(self.customopt[..i].join(".")));
+                            let _ = sender
+                                .output(AppMsg::AddStar(optionpath::join(&self.customopt[..i])));
                         }
                     }
                     for (k, v) in n {
@@ -204,7 +211,7 @@ impl SimpleComponent for SearchEntryModel {
             SearchEntryMsg::SetName(v, i) => {
                 if self.position.get(i).is_some() {
                     if v.is_empty() {
-                        self.customopt[i] = String::from("&lt;name&gt;");
+                        self.customopt[i] = String::from("<name>");
                     } else {
                         self.customopt[i] = v;
                     }
@@ -237,7 +244,7 @@ impl FactoryComponent for SearchEntryOption {
     view! {
         adw::ActionRow {
             #[watch]
-            set_title: &self.value.join("."),
+            set_title: &gtk::glib::markup_escape_text(&optionpath::join(&self.value)),
             set_selectable: false,
             set_activatable: true,
             connect_activated[sender, value = self.value.clone()] => move |_| {
@@ -247,11 +254,9 @@ impl FactoryComponent for SearchEntryOption {
     }
 
     fn init_model(value: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
-        let v = value
-            .split('.')
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>();
-        Self { value: v }
+        Self {
+            value: optionpath::split(&value),
+        }
     }
 
     fn output_to_parent_input(output: Self::Output) -> Option<SearchEntryMsg> {
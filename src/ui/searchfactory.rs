@@ -1,4 +1,5 @@
 use super::searchpage::SearchPageMsg;
+use crate::parse::optionpath;
 use adw::prelude::*;
 use relm4::{factory::*, *};
 
@@ -7,6 +8,10 @@ pub struct SearchOption {
     pub value: Vec<String>,
     pub configured: bool,
     pub modified: bool,
+    /// Byte ranges of the option name that matched the search
+    pub highlights: Vec<(usize, usize)>,
+    /// Additional options file the option was declared in
+    pub source: Option<String>,
 }
 
 #[relm4::factory(pub)]
@@ -27,18 +32,25 @@ impl FactoryComponent for SearchOption {
                 set_spacing: 6,
                 set_margin_all: 15,
                 gtk::Label {
-                    set_text: &self.value.join("."),
+                    set_markup: &highlight(&optionpath::join(&self.value), &self.highlights),
                 },
                 gtk::Separator {
                     set_hexpand: true,
                     set_opacity: 0.0,
                 },
+                gtk::Label {
+                    set_label: self.source.as_deref().unwrap_or_default(),
+                    set_tooltip_text: Some("Declared in an additional options file"),
+                    add_css_class: "caption",
+                    add_css_class: "accent",
+                    set_visible: self.source.is_some(),
+                },
                 gtk::Image {
                     set_icon_name: if self.modified { Some("system-run-symbolic") } else { Some("object-select-symbolic") },
                     set_visible: self.configured || self.modified,
                 },
             },
-            set_title: &self.value.join("."),
+            set_title: &optionpath::join(&self.value),
         }
     }
 
@@ -46,3 +58,23 @@ impl FactoryComponent for SearchOption {
         value
     }
 }
+
+/// Escapes `text` for pango markup and makes the `ranges` bold.
+fn highlight(text: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for &(start, end) in ranges {
+        let start = start.max(pos).min(text.len());
+        let end = end.min(text.len());
+        if start >= end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            continue;
+        }
+        out.push_str(&gtk::glib::markup_escape_text(&text[pos..start]));
+        out.push_str("<b>");
+        out.push_str(&gtk::glib::markup_escape_text(&text[start..end]));
+        out.push_str("</b>");
+        pos = end;
+    }
+    out.push_str(&gtk::glib::markup_escape_text(&text[pos..]));
+    out
+}
@@ -1,22 +1,35 @@
 use super::{searchfactory::SearchOption, window::*};
 use crate::parse::config::opconfigured;
+use crate::parse::optiondb::OptionDb;
+use crate::parse::optionpath;
+use crate::parse::search::{self, Filter, Query};
 use adw::prelude::*;
+use log::info;
 use relm4::{factory::*, *};
-use std::{cmp::Ordering, collections::HashMap};
+use std::{collections::HashMap, convert::identity, sync::Arc};
+
+// Rows are expensive to build, only show the best matches
+const MAXRESULTS: usize = 1000;
 
 #[derive(Debug)]
 pub enum SearchPageMsg {
     Search(String, HashMap<String, String>),
+    DoneSearch(String, Vec<SearchOption>),
     OpenOption(Vec<String>, Option<Vec<String>>),
-    LoadOptions(Vec<(String, bool, String)>),
+    /// Options to search and the values set in the configuration
+    LoadOptions(Arc<OptionDb>, HashMap<String, String>),
     SetModifiedOnly(bool, bool),
+    RemoveFilter(Filter),
+    Complete(String),
 }
 
 pub struct SearchPageModel {
-    pub options: Vec<(String, bool, String)>,
     pub oplst: FactoryVecDeque<SearchOption>,
     query: String,
+    filters: Vec<Filter>,
+    suggestions: Vec<String>,
     modifiedonly: bool,
+    async_handler: WorkerController<SearchAsyncHandler>,
 }
 
 #[relm4::component(pub)]
@@ -27,36 +40,52 @@ impl SimpleComponent for SearchPageModel {
     type Widgets = SearchPageWidgets;
 
     view! {
-        view = gtk::Stack {
-            set_transition_type: gtk::StackTransitionType::Crossfade,
-            #[name(options)]
-            adw::PreferencesPage {
-                set_title: "Attributes",
-                add = &adw::PreferencesGroup {
-                    set_title: "Options",
-                    #[local_ref]
-                    add = oplstbox -> gtk::ListBox {
-                        add_css_class: "boxed-list",
-                        set_selection_mode: gtk::SelectionMode::None,
-                        connect_row_activated[sender] => move |_, y| {
-                            if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
-                                let text = l.title().to_string();
-                                let v = text.split('.').map(|x| x.to_string()).collect::<Vec<String>>();
-                                sender.input(SearchPageMsg::OpenOption(v, None));
-                            }
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            #[name(filterbox)]
+            gtk::FlowBox {
+                set_selection_mode: gtk::SelectionMode::None,
+                set_column_spacing: 6,
+                set_row_spacing: 6,
+                set_margin_start: 15,
+                set_margin_end: 15,
+                set_margin_top: 10,
+                #[watch]
+                set_visible: !model.filters.is_empty() || !model.suggestions.is_empty(),
+            },
+            #[name(view)]
+            gtk::Stack {
+                set_vexpand: true,
+                set_transition_type: gtk::StackTransitionType::Crossfade,
+                #[name(options)]
+                adw::PreferencesPage {
+                    set_title: "Attributes",
+                    add = &adw::PreferencesGroup {
+                        set_title: "Options",
+                        #[local_ref]
+                        add = oplstbox -> gtk::ListBox {
+                            add_css_class: "boxed-list",
+                            set_selection_mode: gtk::SelectionMode::None,
+                            connect_row_activated[sender] => move |_, y| {
+                                if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
+                                    let text = l.title().to_string();
+                                    let v = optionpath::split(&text);
+                                    sender.input(SearchPageMsg::OpenOption(v, None));
+                                }
+                            },
                         },
+                    }
+                },
+                #[name(empty)]
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+                    adw::StatusPage {
+                        set_icon_name: Some("edit-find-symbolic"),
+                        set_title: "No options found!",
+                        set_description: Some("Try a different search"),
                     },
                 }
-            },
-            #[name(empty)]
-            gtk::Box {
-                set_orientation: gtk::Orientation::Vertical,
-                set_valign: gtk::Align::Center,
-                adw::StatusPage {
-                    set_icon_name: Some("edit-find-symbolic"),
-                    set_title: "No options found!",
-                    set_description: Some("Try a different search"),
-                },
             }
         }
     }
@@ -67,6 +96,35 @@ impl SimpleComponent for SearchPageModel {
         } else {
             view.set_visible_child(options);
         }
+        while let Some(child) = filterbox.first_child() {
+            filterbox.remove(&child);
+        }
+        for filter in &model.filters {
+            let chip = gtk::Button::new();
+            chip.add_css_class("pill");
+            chip.add_css_class("small");
+            let chipbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            chipbox.append(&gtk::Label::new(Some(&filter.to_string())));
+            chipbox.append(&gtk::Image::from_icon_name("window-close-symbolic"));
+            chip.set_child(Some(&chipbox));
+            chip.set_tooltip_text(Some("Remove filter"));
+            let chipsender = sender.clone();
+            let filter = filter.clone();
+            chip.connect_clicked(move |_| {
+                chipsender.input(SearchPageMsg::RemoveFilter(filter.clone()));
+            });
+            filterbox.insert(&chip, -1);
+        }
+        for suggestion in &model.suggestions {
+            let btn = gtk::Button::with_label(suggestion);
+            btn.add_css_class("flat");
+            let btnsender = sender.clone();
+            let suggestion = suggestion.to_string();
+            btn.connect_clicked(move |_| {
+                btnsender.input(SearchPageMsg::Complete(suggestion.clone()));
+            });
+            filterbox.insert(&btn, -1);
+        }
     }
 
     fn init(
@@ -74,11 +132,16 @@ impl SimpleComponent for SearchPageModel {
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let async_handler = SearchAsyncHandler::builder()
+            .detach_worker(())
+            .forward(sender.input_sender(), identity);
         let model = SearchPageModel {
-            options: vec![],
             oplst: FactoryVecDeque::new(gtk::ListBox::new(), sender.input_sender()),
             query: String::default(),
+            filters: vec![],
+            suggestions: vec![],
             modifiedonly: false,
+            async_handler,
         };
         let oplstbox = model.oplst.widget();
 
@@ -92,64 +155,20 @@ impl SimpleComponent for SearchPageModel {
         match msg {
             SearchPageMsg::Search(query, editedopts) => {
                 self.query = query.to_string();
-                oplst_guard.clear();
-                let q = query.split_whitespace();
-                let mut sortedoptions = self.options.clone();
-                if q.clone().any(|x| x.len() > 2) {
-                    sortedoptions = sortedoptions
-                        .iter()
-                        .filter(|x| {
-                            for part in q.clone() {
-                                if x.0.to_lowercase().contains(&part.to_lowercase()) {
-                                    return true;
-                                }
-                            }
-                            false
-                        })
-                        .map(|x| x.to_owned())
-                        .collect::<Vec<_>>();
-                    sortedoptions.sort_by(|a, b| {
-                        let mut acount = 0;
-                        let mut bcount = 0;
-                        for part in q.clone() {
-                            acount += a.0.to_lowercase().matches(&part.to_lowercase()).count();
-                            bcount += b.0.to_lowercase().matches(&part.to_lowercase()).count();
-                        }
-                        match acount.cmp(&bcount) {
-                            Ordering::Less => Ordering::Less,
-                            Ordering::Greater => Ordering::Greater,
-                            Ordering::Equal => a.0.len().cmp(&b.0.len()),
-                        }
-                    });
-                } else {
-                    sortedoptions.sort_by(|a, b| a.0.len().cmp(&b.0.len()));
-                }
-                for opt in sortedoptions {
-                    if q.clone().all(|part| {
-                        opt.0.to_lowercase().contains(&part.to_lowercase())
-                            || if q.clone().any(|x| x.len() > 2) {
-                                opt.2.to_lowercase().contains(&part.to_lowercase())
-                            } else {
-                                false
-                            }
-                    }) {
-                        let configured = opt.1;
-                        let modified = opconfigured(&editedopts, &[], opt.0.clone());
-                        if self.modifiedonly && !(configured || modified) {
-                            continue;
-                        }
-                        oplst_guard.push_back(SearchOption {
-                            value: opt
-                                .0
-                                .split('.')
-                                .map(|s| s.to_string())
-                                .collect::<Vec<String>>(),
-                            configured,
-                            modified,
-                        });
-                    }
-                    if oplst_guard.len() >= 1000 {
-                        break;
+                self.filters = Query::parse(&query).filters;
+                self.suggestions = search::suggestions(&query);
+                self.async_handler.emit(SearchAsyncHandlerMsg::Search(
+                    query,
+                    editedopts,
+                    self.modifiedonly,
+                ));
+            }
+            SearchPageMsg::DoneSearch(query, results) => {
+                // Results of an older query may arrive after a newer one was sent
+                if query == self.query {
+                    oplst_guard.clear();
+                    for result in results {
+                        oplst_guard.push_back(result);
                     }
                 }
             }
@@ -163,8 +182,18 @@ impl SimpleComponent for SearchPageModel {
                     ));
                 }
             }
-            SearchPageMsg::LoadOptions(options) => {
-                self.options = options;
+            SearchPageMsg::LoadOptions(db, conf) => {
+                self.async_handler
+                    .emit(SearchAsyncHandlerMsg::LoadOptions(db, conf));
+            }
+            SearchPageMsg::RemoveFilter(filter) => {
+                let _ = sender.output(AppMsg::SetSearchText(search::removefilter(
+                    &self.query,
+                    &filter,
+                )));
+            }
+            SearchPageMsg::Complete(word) => {
+                let _ = sender.output(AppMsg::SetSearchText(search::complete(&self.query, &word)));
             }
             SearchPageMsg::SetModifiedOnly(modified, search) => {
                 self.modifiedonly = modified;
@@ -175,3 +204,68 @@ impl SimpleComponent for SearchPageModel {
         }
     }
 }
+
+pub struct SearchAsyncHandler {
+    db: Arc<OptionDb>,
+    conf: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub enum SearchAsyncHandlerMsg {
+    LoadOptions(Arc<OptionDb>, HashMap<String, String>),
+    Search(String, HashMap<String, String>, bool),
+}
+
+impl Worker for SearchAsyncHandler {
+    type Init = ();
+    type Input = SearchAsyncHandlerMsg;
+    type Output = SearchPageMsg;
+
+    fn init(_params: Self::Init, _sender: relm4::ComponentSender<Self>) -> Self {
+        Self {
+            db: Arc::new(OptionDb::default()),
+            conf: HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            SearchAsyncHandlerMsg::LoadOptions(db, conf) => {
+                info!("Received LoadOptions message");
+                self.db = db;
+                self.conf = conf;
+            }
+            SearchAsyncHandlerMsg::Search(query, editedopts, modifiedonly) => {
+                info!("Received Search message");
+                let set = |values: &HashMap<String, String>, name: &str| {
+                    let mut pos = optionpath::split(name);
+                    let attr = pos.pop().unwrap_or_default();
+                    opconfigured(values, &pos, attr)
+                };
+                let results = self
+                    .db
+                    .search(&Query::parse(&query), |name| {
+                        set(&self.conf, name) || set(&editedopts, name)
+                    })
+                    .into_iter()
+                    .filter_map(|result| {
+                        let configured = set(&self.conf, &result.name);
+                        let modified = set(&editedopts, &result.name);
+                        if modifiedonly && !(configured || modified) {
+                            return None;
+                        }
+                        Some(SearchOption {
+                            value: optionpath::split(&result.name),
+                            configured,
+                            modified,
+                            source: self.db.source(&result.name).map(|x| x.to_string()),
+                            highlights: result.highlights,
+                        })
+                    })
+                    .take(MAXRESULTS)
+                    .collect::<Vec<_>>();
+                let _ = sender.output(SearchPageMsg::DoneSearch(query, results));
+            }
+        }
+    }
+}
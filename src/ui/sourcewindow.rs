@@ -0,0 +1,166 @@
+use super::window::AppMsg;
+use adw::prelude::*;
+use gtk::gio;
+use log::{info, warn};
+use relm4::*;
+use sourceview5::prelude::*;
+use std::{env, fs, path::Path, path::PathBuf, process::Command};
+
+#[tracker::track]
+pub struct SourceWindowModel {
+    hidden: bool,
+    path: PathBuf,
+    text: String,
+    line: usize,
+    scheme: Option<sourceview5::StyleScheme>,
+}
+
+#[derive(Debug)]
+pub enum SourceWindowMsg {
+    Show(PathBuf, usize),
+    OpenExternal,
+    Close,
+    SetScheme(String),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for SourceWindowModel {
+    type Init = gtk::Window;
+    type Input = SourceWindowMsg;
+    type Output = AppMsg;
+    type Widgets = SourceWindowWidgets;
+
+    view! {
+        window = adw::Window {
+            set_transient_for: Some(&parent_window),
+            set_default_width: 800,
+            set_default_height: 600,
+            #[watch]
+            set_visible: !model.hidden,
+            connect_close_request[sender] => move |_| {
+                sender.input(SourceWindowMsg::Close);
+                gtk::Inhibit(true)
+            },
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                adw::HeaderBar {
+                    #[wrap(Some)]
+                    set_title_widget = &adw::WindowTitle {
+                        #[watch]
+                        set_title: &model.path.file_name().unwrap_or_default().to_string_lossy(),
+                        #[watch]
+                        set_subtitle: &model.path.to_string_lossy(),
+                    },
+                    pack_start = &gtk::Button {
+                        set_icon_name: "document-edit-symbolic",
+                        set_tooltip_text: Some("Open in editor"),
+                        connect_clicked[sender] => move |_| {
+                            sender.input(SourceWindowMsg::OpenExternal);
+                        }
+                    },
+                },
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    #[name(view)]
+                    sourceview5::View {
+                        set_editable: false,
+                        set_monospace: true,
+                        set_show_line_numbers: true,
+                        set_highlight_current_line: true,
+                        set_top_margin: 5,
+                        set_bottom_margin: 5,
+                        set_left_margin: 5,
+                        #[wrap(Some)]
+                        set_buffer: sourcebuf = &sourceview5::Buffer {
+                            set_language: sourceview5::LanguageManager::default().language("nix").as_ref(),
+                            #[track(model.changed(SourceWindowModel::scheme()))]
+                            set_style_scheme: model.scheme.as_ref(),
+                            #[track(model.changed(SourceWindowModel::text()))]
+                            set_text: &model.text,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn pre_view() {
+        if model.changed(SourceWindowModel::line()) || model.changed(SourceWindowModel::text()) {
+            if let Some(iter) = sourcebuf.iter_at_line(model.line.saturating_sub(1) as i32) {
+                sourcebuf.place_cursor(&iter);
+                view.scroll_to_mark(&sourcebuf.get_insert(), 0.0, true, 0.0, 0.3);
+            }
+        }
+    }
+
+    fn init(
+        parent_window: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = SourceWindowModel {
+            hidden: true,
+            path: PathBuf::new(),
+            text: String::new(),
+            line: 1,
+            scheme: None,
+            tracker: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        self.reset();
+        match msg {
+            SourceWindowMsg::Show(path, line) => {
+                info!("SourceWindowMsg::Show");
+                match fs::read_to_string(&path) {
+                    Ok(text) => {
+                        self.set_text(text);
+                        self.set_path(path);
+                        self.update_line(|x| *x = line);
+                        self.set_hidden(false);
+                    }
+                    Err(e) => warn!("Failed to read {}: {}", path.to_string_lossy(), e),
+                }
+            }
+            SourceWindowMsg::OpenExternal => {
+                info!("SourceWindowMsg::OpenExternal");
+                openexternal(&self.path, self.line);
+            }
+            SourceWindowMsg::Close => {
+                self.set_hidden(true);
+            }
+            SourceWindowMsg::SetScheme(scheme) => {
+                self.set_scheme(sourceview5::StyleSchemeManager::default().scheme(&scheme));
+            }
+        }
+    }
+}
+
+/// Opens `path` in `$VISUAL` at `line`, or in the default application for the file.
+pub fn openexternal(path: &Path, line: usize) {
+    if let Ok(visual) = env::var("VISUAL") {
+        let mut args = visual.split_whitespace();
+        if let Some(editor) = args.next() {
+            match Command::new(editor)
+                .args(args)
+                .arg(format!("+{}", line))
+                .arg(path)
+                .spawn()
+            {
+                Ok(_) => return,
+                Err(e) => warn!("Failed to run {}: {}", editor, e),
+            }
+        }
+    }
+    if let Err(e) = gio::AppInfo::launch_default_for_uri(
+        &gio::File::for_path(path).uri(),
+        gio::AppLaunchContext::NONE,
+    ) {
+        warn!("Failed to open {}: {}", path.to_string_lossy(), e);
+    }
+}
@@ -1,4 +1,5 @@
 use super::window::*;
+use crate::parse::optionpath;
 use adw::prelude::*;
 use relm4::{factory::*, *};
 
@@ -9,6 +10,8 @@ pub struct AttrPos {
     pub configured: bool,
     pub modified: bool,
     pub replacefor: Option<String>,
+    /// Additional options file every option below was declared in
+    pub source: Option<String>,
 }
 
 #[relm4::factory(pub)]
@@ -33,7 +36,7 @@ impl FactoryComponent for AttrPos {
                         if self.replacefor == Some(String::from("*")) {
                                     format!("[<i>{}</i>]", self.value.last().unwrap_or(&String::new()))
                                 } else {
-                                    self.value.last().unwrap_or(&String::new()).to_string()
+                                    gtk::glib::markup_escape_text(self.value.last().unwrap_or(&String::new())).to_string()
                                 }
                     },
                     set_use_markup: true,
@@ -42,12 +45,19 @@ impl FactoryComponent for AttrPos {
                     set_hexpand: true,
                     set_opacity: 0.0,
                 },
+                gtk::Label {
+                    set_label: self.source.as_deref().unwrap_or_default(),
+                    set_tooltip_text: Some("Declared in an additional options file"),
+                    add_css_class: "caption",
+                    add_css_class: "accent",
+                    set_visible: self.source.is_some(),
+                },
                 gtk::Image {
                     set_icon_name: if self.modified { Some("system-run-symbolic") } else { Some("object-select-symbolic") },
                     set_visible: self.configured || self.modified,
                 },
             },
-            set_title: &self.value.join("."),
+            set_title: &optionpath::join(&self.value),
         }
     }
 
@@ -58,6 +68,7 @@ impl FactoryComponent for AttrPos {
             configured: parent.configured,
             modified: parent.modified,
             replacefor: parent.replacefor,
+            source: parent.source,
         }
     }
 }
@@ -68,6 +79,10 @@ pub struct OptPos {
     pub refvalue: Vec<String>,
     pub configured: bool,
     pub modified: bool,
+    /// Set to an explicit `null`
+    pub null: bool,
+    /// Additional options file the option was declared in
+    pub source: Option<String>,
 }
 
 #[relm4::factory(pub)]
@@ -96,12 +111,25 @@ impl FactoryComponent for OptPos {
                     set_hexpand: true,
                     set_opacity: 0.0,
                 },
+                gtk::Label {
+                    set_label: self.source.as_deref().unwrap_or_default(),
+                    set_tooltip_text: Some("Declared in an additional options file"),
+                    add_css_class: "caption",
+                    add_css_class: "accent",
+                    set_visible: self.source.is_some(),
+                },
+                gtk::Label {
+                    set_label: "null",
+                    add_css_class: "dim-label",
+                    add_css_class: "monospace",
+                    set_visible: self.null,
+                },
                 gtk::Image {
                     set_icon_name: if self.modified { Some("system-run-symbolic") } else { Some("object-select-symbolic") },
                     set_visible: self.configured || self.modified,
                 },
             },
-            set_title: &self.value.join("."),
+            set_title: &optionpath::join(&self.value),
         }
     }
 
@@ -111,6 +139,8 @@ impl FactoryComponent for OptPos {
             refvalue: parent.refvalue,
             configured: parent.configured,
             modified: parent.modified,
+            null: parent.null,
+            source: parent.source,
         }
     }
 }
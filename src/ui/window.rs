@@ -1,10 +1,13 @@
 use super::about::AboutPageModel;
+use super::bookmarks::{BookmarksModel, BookmarksMsg};
 use super::nameentry::NameEntryModel;
 use super::optionpage::*;
+use super::overview::{OverviewModel, OverviewMsg};
 use super::preferencespage::PreferencesPageModel;
 use super::rebuild::RebuildModel;
 use super::savechecking::SaveErrorModel;
 use super::savechecking::SaveErrorMsg;
+use super::savechecking::{BatchCheckHandler, BatchCheckHandlerMsg, CheckEntry};
 use super::searchentry::SearchEntryModel;
 use super::welcome::WelcomeModel;
 use super::welcome::WelcomeMsg;
@@ -15,49 +18,54 @@ use super::{
     searchpage::{SearchPageModel, SearchPageMsg},
     treefactory::*,
 };
-use crate::parse::config;
-use crate::parse::config::getarrvals;
 use crate::parse::config::getconfvals;
-use crate::parse::config::opconfigured2;
-use crate::parse::config::readval;
+use crate::parse::config::opconfigured;
+use crate::parse::document::ConfigDocument;
+use crate::parse::optiondb::OptionDb;
+use crate::parse::optionpath;
+use crate::parse::options::{innerpath, isnested};
 use crate::parse::preferences::getconfig;
-use crate::parse::{
-    config::{opconfigured, parseconfig},
-    options::*,
-};
+use crate::parse::syntax::ConfigError;
 use crate::ui::nameentry::NameEntryMsg;
 use crate::ui::preferencespage::PreferencesPageMsg;
 use crate::ui::quitdialog::{QuitCheckModel, QuitCheckMsg};
 use crate::ui::rebuild::RebuildMsg;
 use crate::ui::searchentry::SearchEntryMsg;
+use crate::ui::sourcewindow::{SourceWindowModel, SourceWindowMsg};
 use crate::ui::windowloading::LoadErrorMsg;
 use adw::prelude::*;
 use log::*;
 use nix_data::config::configfile::NixDataConfig;
+use relm4::gtk::gio;
 use relm4::gtk::glib::object::Cast;
 use relm4::{actions::*, factory::*, *};
 use std::collections::HashMap;
 use std::convert::identity;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 #[tracker::track]
 pub struct AppModel {
     mainwindow: adw::ApplicationWindow,
     pub position: Vec<String>,
     pub refposition: Vec<String>,
-    tree: AttrTree,
+    /// Every declared option, shared with the pages and workers that query it
+    #[tracker::no_eq]
+    db: Arc<OptionDb>,
     #[tracker::no_eq]
     attributes: FactoryVecDeque<AttrPos>,
     #[tracker::no_eq]
     options: FactoryVecDeque<OptPos>,
     #[tracker::no_eq]
     posbtn: FactoryVecDeque<AttrBtn>,
-    pub conf: HashMap<String, String>,
+    /// The configuration file as it was last loaded, with the pending edits applied
+    #[tracker::no_eq]
+    document: ConfigDocument,
     page: Page,
     header: HeaderBar,
     search: bool,
+    searchtext: String,
     busy: bool,
-    pub data: HashMap<String, OptionData>,
-    pub editedopts: HashMap<String, String>,
     nameattrs: HashMap<String, Vec<String>>,
     starattrs: HashMap<String, usize>,
     pub scheme: Option<sourceview5::StyleScheme>,
@@ -65,6 +73,24 @@ pub struct AppModel {
     nameorstar: AddAttrOptions,
     config: NixDataConfig,
     modifiedonly: bool,
+    /// Places to go back to, most recent last
+    back: Vec<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
+    current: Option<HistoryEntry>,
+    /// Position listed in the tree, which stays beside the option page on wide windows
+    treeposition: Vec<String>,
+    treerefposition: Vec<String>,
+    /// Whether the window is too narrow to show the tree and the option page side by side
+    folded: bool,
+    optionopen: bool,
+    #[tracker::no_eq]
+    tabview: adw::TabView,
+    /// Option pages in the main window and in detached windows
+    #[tracker::no_eq]
+    tabs: Vec<OptionTab>,
+    nexttab: usize,
+    /// Tab whose save failed and awaits a decision in the save error dialog
+    errortab: Option<usize>,
 
     // Components
     #[tracker::no_eq]
@@ -72,16 +98,22 @@ pub struct AppModel {
     #[tracker::no_eq]
     windowloading: WorkerController<WindowAsyncHandler>,
     #[tracker::no_eq]
-    loaderror: Controller<LoadErrorModel>,
+    batchcheck: WorkerController<BatchCheckHandler>,
     #[tracker::no_eq]
-    optionpage: Controller<OptPageModel>,
+    loaderror: Controller<LoadErrorModel>,
     #[tracker::no_eq]
     searchpage: Controller<SearchPageModel>,
     #[tracker::no_eq]
+    overview: Controller<OverviewModel>,
+    #[tracker::no_eq]
     saveerror: Controller<SaveErrorModel>,
     #[tracker::no_eq]
     rebuild: Controller<RebuildModel>,
     #[tracker::no_eq]
+    sourcewindow: Controller<SourceWindowModel>,
+    #[tracker::no_eq]
+    bookmarks: Controller<BookmarksModel>,
+    #[tracker::no_eq]
     welcome: Controller<WelcomeModel>,
     #[tracker::no_eq]
     nameentry: Controller<NameEntryModel>,
@@ -91,11 +123,19 @@ pub struct AppModel {
     quitdialog: Controller<QuitCheckModel>,
 }
 
+/// An option page shown in a tab of the main window or of a detached window.
+struct OptionTab {
+    id: usize,
+    page: adw::TabPage,
+    controller: Controller<OptPageModel>,
+    opt: Vec<String>,
+    refopt: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct LoadValues {
-    pub data: HashMap<String, OptionData>,
-    pub tree: AttrTree,
-    pub conf: HashMap<String, String>,
+    pub db: OptionDb,
+    pub document: ConfigDocument,
 }
 
 #[derive(Debug, PartialEq)]
@@ -109,6 +149,7 @@ enum AddAttrOptions {
 pub enum AppMsg {
     Welcome,
     InitialLoad(LoadValues),
+    ConfigReloaded(ConfigDocument),
     LoadError(String, String),
     TryLoad,
     Close,
@@ -117,19 +158,34 @@ pub enum AppMsg {
     MoveToSelf,
     MoveToRow(Vec<String>),
     OpenOption(Vec<String>, Vec<String>),
-    OpenOptionRow(Vec<String>),
+    OpenOptionTab(Vec<String>, Vec<String>),
+    OpenOptionRow(Vec<String>, bool),
+    NewTab,
+    CloseTab(adw::TabPage),
+    /// Closes the selected tab of the main window or of a detached tab window
+    CloseSelectedTab(adw::TabView),
+    SelectTab,
+    ShowOverview,
+    OpenConfigured(String),
+    ResetOpts(Vec<String>),
     ShowSearch,
     HideSearch,
     ToggleSearch,
     ShowSearchPage(String),
+    SetSearchText(String),
     HideSearchPage,
     ShowSearchPageEntry(Vec<String>),
     SetBusy(bool),
-    SaveError(String),
+    SaveError(usize, String),
     SaveWithError,
     SaveErrorReset,
     EditOpt(String, String),
+    EditNote(String, String),
+    ShowSource(PathBuf, usize),
     Rebuild,
+    CheckConfig,
+    DoneBatchCheck(Vec<(String, String)>),
+    StartRebuild,
     SaveConfig,
     ResetConfig,
     ShowPrefMenu,
@@ -142,12 +198,25 @@ pub enum AppMsg {
     SaveQuit,
     ShowAboutPage,
     SetModifiedOnly(bool),
+    GoBack,
+    GoForward,
+    SetFolded(bool),
+}
+
+/// A place in the window the navigation history can return to.
+#[derive(PartialEq, Debug, Clone)]
+enum HistoryEntry {
+    Tree(Vec<String>, Vec<String>),
+    Option(Vec<String>, Vec<String>),
+    Search(String),
+    Overview,
 }
 
 #[derive(PartialEq, Debug)]
 enum Page {
     List,
     Option,
+    Overview,
     Loading,
 }
 
@@ -245,12 +314,34 @@ impl SimpleComponent for AppModel {
                             });
                         },
                     },
+                    pack_start = &gtk::Box {
+                        add_css_class: "linked",
+                        gtk::Button {
+                            set_icon_name: "go-previous-symbolic",
+                            set_tooltip_text: Some("Back"),
+                            #[watch]
+                            set_sensitive: !model.back.is_empty(),
+                            connect_clicked[sender] => move |_| {
+                                sender.input(AppMsg::GoBack);
+                            },
+                        },
+                        gtk::Button {
+                            set_icon_name: "go-next-symbolic",
+                            set_tooltip_text: Some("Forward"),
+                            #[watch]
+                            set_sensitive: !model.forward.is_empty(),
+                            connect_clicked[sender] => move |_| {
+                                sender.input(AppMsg::GoForward);
+                            },
+                        },
+                    },
                     pack_start = &gtk::Button {
                         set_label: "Rebuild",
                         connect_clicked[sender] => move |_| {
                             sender.input(AppMsg::Rebuild);
                         },
-                    }
+                    },
+                    pack_end: model.bookmarks.widget(),
                 },
                 #[name(stack)]
                 gtk::Stack {
@@ -269,67 +360,116 @@ impl SimpleComponent for AppModel {
                             set_label: "Loading...",
                         },
                     },
-                    #[name(treeview)]
-                    adw::PreferencesPage {
-                        add: attrgroup = &adw::PreferencesGroup {
-                            set_title: "Attributes",
-                            #[track(model.changed(AppModel::position()))]
-                            set_visible: !model.attributes.is_empty() || model.nameorstar != AddAttrOptions::None,
-                            #[local_ref]
-                            add = attrlistbox -> gtk::ListBox {
-                                add_css_class: "boxed-list",
-                                set_selection_mode: gtk::SelectionMode::None,
-                                append: addrow = &adw::PreferencesRow { // Change to suffix once libadwaita-rs 0.2 is out
-                                    #[track(model.changed(AppModel::nameorstar()))]
-                                    set_visible: model.nameorstar != AddAttrOptions::None,
-                                    set_title: "<ADD>",
-                                    #[wrap(Some)]
-                                    set_child = &gtk::Box {
-                                        set_margin_all: 15,
-                                        gtk::Image {
-                                            set_halign: gtk::Align::Center,
-                                            set_hexpand: true,
-                                            set_icon_name: Some("list-add-symbolic"),
-                                            add_css_class: "accent",
+                    #[name(browser)]
+                    adw::Leaflet {
+                        set_can_navigate_back: false,
+                        set_transition_type: adw::LeafletTransitionType::Slide,
+                        connect_folded_notify[sender] => move |x| {
+                            sender.input(AppMsg::SetFolded(x.is_folded()));
+                        },
+                        #[name(treeview)]
+                        adw::PreferencesPage {
+                            set_width_request: 360,
+                            add: attrgroup = &adw::PreferencesGroup {
+                                set_title: "Attributes",
+                                #[track(model.changed(AppModel::position()))]
+                                set_visible: !model.attributes.is_empty() || model.nameorstar != AddAttrOptions::None,
+                                #[local_ref]
+                                add = attrlistbox -> gtk::ListBox {
+                                    add_css_class: "boxed-list",
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                    append: addrow = &adw::PreferencesRow { // Change to suffix once libadwaita-rs 0.2 is out
+                                        #[track(model.changed(AppModel::nameorstar()))]
+                                        set_visible: model.nameorstar != AddAttrOptions::None,
+                                        set_title: "<ADD>",
+                                        #[wrap(Some)]
+                                        set_child = &gtk::Box {
+                                            set_margin_all: 15,
+                                            gtk::Image {
+                                                set_halign: gtk::Align::Center,
+                                                set_hexpand: true,
+                                                set_icon_name: Some("list-add-symbolic"),
+                                                add_css_class: "accent",
+                                            }
                                         }
-                                    }
-                                },
-                                connect_row_activated[sender] => move |_, y| {
-                                    if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
-                                        if l.title() != "<ADD>" {
-                                            let text = l.title().to_string();
-                                            let v = text.split('.').map(|x| x.to_string()).collect::<Vec<String>>();
-                                            sender.input(AppMsg::MoveToRow(v));
-                                        } else {
-                                            sender.input(AppMsg::AddAttr);
+                                    },
+                                    connect_row_activated[sender] => move |_, y| {
+                                        if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
+                                            if l.title() != "<ADD>" {
+                                                let text = l.title().to_string();
+                                                let v = optionpath::split(&text);
+                                                sender.input(AppMsg::MoveToRow(v));
+                                            } else {
+                                                sender.input(AppMsg::AddAttr);
+                                            }
                                         }
-                                    }
+                                    },
                                 },
                             },
+                            add = &adw::PreferencesGroup {
+                                set_title: "Options",
+                                #[track(model.changed(AppModel::position()))]
+                                set_visible: !model.options.is_empty(),
+                                #[local_ref]
+                                add = optlistbox -> gtk::ListBox {
+                                    add_css_class: "boxed-list",
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                    connect_row_activated[sender] => move |_, y| {
+                                         if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
+                                            let text = l.title().to_string();
+                                            let v = optionpath::split(&text);
+                                            sender.input(AppMsg::OpenOptionRow(v, false))
+                                         }
+                                    },
+                                },
+                            }
                         },
-                        add = &adw::PreferencesGroup {
-                            set_title: "Options",
-                            #[track(model.changed(AppModel::position()))]
-                            set_visible: !model.options.is_empty(),
-                            #[local_ref]
-                            add = optlistbox -> gtk::ListBox {
-                                add_css_class: "boxed-list",
-                                set_selection_mode: gtk::SelectionMode::None,
-                                connect_row_activated[sender] => move |_, y| {
-                                     if let Ok(l) = y.clone().downcast::<adw::PreferencesRow>() {
-                                        let text = l.title().to_string();
-                                        let v = text.split('.').map(|x| x.to_string()).collect::<Vec<String>>();
-                                        sender.input(AppMsg::OpenOptionRow(v))
-                                     }
+                        gtk::Separator {
+                            set_orientation: gtk::Orientation::Vertical,
+                            #[watch]
+                            set_visible: !model.folded,
+                        },
+                        #[name(optpage)]
+                        gtk::Stack {
+                            set_hexpand: true,
+                            set_width_request: 400,
+                            #[name(optionbox)]
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                adw::TabBar {
+                                    set_view: Some(tabview),
+                                    set_autohide: true,
+                                    #[wrap(Some)]
+                                    set_end_action_widget = &gtk::Button {
+                                        set_icon_name: "tab-new-symbolic",
+                                        set_tooltip_text: Some("Open in new tab"),
+                                        add_css_class: "flat",
+                                        connect_clicked[sender] => move |_| {
+                                            sender.input(AppMsg::NewTab);
+                                        }
+                                    },
+                                },
+                                #[local_ref]
+                                tabview -> adw::TabView {
+                                    set_vexpand: true,
+                                    connect_selected_page_notify[sender] => move |_| {
+                                        sender.input(AppMsg::SelectTab);
+                                    },
+                                    connect_n_pages_notify[sender] => move |_| {
+                                        sender.input(AppMsg::SelectTab);
+                                    },
                                 },
                             },
-                        }
-                    },
-                    #[name(optpage)]
-                    gtk::Box {
-                        append: model.optionpage.widget()
+                            #[name(nooption)]
+                            adw::StatusPage {
+                                set_icon_name: Some("document-properties-symbolic"),
+                                set_title: "No option selected",
+                                set_description: Some("Select an option from the list to view and edit it"),
+                            },
+                        },
                     },
-                    add_titled: (model.searchpage.widget(), Some("SearchPage"), "SearchPage")
+                    add_titled: (model.searchpage.widget(), Some("SearchPage"), "SearchPage"),
+                    add_titled: (model.overview.widget(), Some("Overview"), "Overview"),
                 }
             },
         }
@@ -338,6 +478,8 @@ impl SimpleComponent for AppModel {
     menu! {
         main_menu: {
             "Modified only" => ModifiedAction(1_u8),
+            "Configured options" => OverviewAction,
+            "Check configuration" => CheckConfigAction,
             "Preferences" => PreferencesAction,
             "About" => AboutAction,
         }
@@ -346,13 +488,32 @@ impl SimpleComponent for AppModel {
     fn pre_view() {
         buttonsbox.remove(homebtn);
         buttonsbox.prepend(homebtn);
+        if model.changed(AppModel::searchtext()) {
+            search.set_text(&model.searchtext);
+            let _ = search.grab_focus();
+            search.set_position(-1);
+        }
         if !model.search {
             search.set_text("");
             match model.page {
-                Page::List => stack.set_visible_child(treeview),
-                Page::Option => stack.set_visible_child(optpage), //stack.set_visible_child(&stack.child_by_name("OptPage").unwrap()),
+                Page::List => {
+                    stack.set_visible_child(browser);
+                    browser.set_visible_child(treeview);
+                }
+                Page::Option => {
+                    stack.set_visible_child(browser);
+                    browser.set_visible_child(optpage);
+                }
+                Page::Overview => {
+                    stack.set_visible_child(&stack.child_by_name("Overview").unwrap())
+                }
                 Page::Loading => stack.set_visible_child(loading),
             }
+            if model.optionopen {
+                optpage.set_visible_child(optionbox);
+            } else {
+                optpage.set_visible_child(nooption);
+            }
         } else {
             stack.set_visible_child(&stack.child_by_name("SearchPage").unwrap());
         }
@@ -380,21 +541,30 @@ impl SimpleComponent for AppModel {
         let windowloading = WindowAsyncHandler::builder()
             .detach_worker(())
             .forward(sender.input_sender(), identity);
+        let batchcheck = BatchCheckHandler::builder()
+            .detach_worker(())
+            .forward(sender.input_sender(), identity);
         let loaderror = LoadErrorModel::builder()
             .launch(root.clone().upcast())
             .forward(sender.input_sender(), identity);
-        let optionpage = OptPageModel::builder()
-            .launch(())
-            .forward(sender.input_sender(), identity);
         let searchpage = SearchPageModel::builder()
             .launch(())
             .forward(sender.input_sender(), identity);
+        let overview = OverviewModel::builder()
+            .launch(root.clone().upcast())
+            .forward(sender.input_sender(), identity);
         let saveerror = SaveErrorModel::builder()
             .launch(root.clone().upcast())
             .forward(sender.input_sender(), identity);
         let rebuild = RebuildModel::builder()
             .launch(root.clone().upcast())
             .forward(sender.input_sender(), identity);
+        let sourcewindow = SourceWindowModel::builder()
+            .launch(root.clone().upcast())
+            .forward(sender.input_sender(), identity);
+        let bookmarks = BookmarksModel::builder()
+            .launch(())
+            .forward(sender.input_sender(), identity);
         let welcome = WelcomeModel::builder()
             .launch(root.clone().upcast())
             .forward(sender.input_sender(), identity);
@@ -414,20 +584,19 @@ impl SimpleComponent for AppModel {
             mainwindow: root.clone(),
             position: vec![],
             refposition: vec![],
-            tree: AttrTree::default(),
+            db: Arc::new(OptionDb::default()),
             attributes: FactoryVecDeque::new(gtk::ListBox::new(), sender.input_sender()),
             options: FactoryVecDeque::new(gtk::ListBox::new(), sender.input_sender()),
             posbtn: FactoryVecDeque::new(
                 gtk::Box::new(gtk::Orientation::Horizontal, 0),
                 sender.input_sender(),
             ),
-            conf: HashMap::new(),
+            document: ConfigDocument::default(),
             page: Page::Loading,
             search: false,
+            searchtext: String::new(),
             busy: true,
             header: HeaderBar::Title,
-            data: HashMap::new(),
-            editedopts: HashMap::new(),
             nameattrs: HashMap::new(),
             starattrs: HashMap::new(),
             config: if let Some(cfg) = config {
@@ -441,16 +610,30 @@ impl SimpleComponent for AppModel {
                 }
             },
             modifiedonly: false,
+            back: vec![],
+            forward: vec![],
+            current: None,
+            treeposition: vec![],
+            treerefposition: vec![],
+            folded: false,
+            optionopen: false,
+            tabview: adw::TabView::new(),
+            tabs: vec![],
+            nexttab: 0,
+            errortab: None,
             scheme: None,
             fieldreplace: HashMap::new(),
             nameorstar: AddAttrOptions::None,
             preferencespage,
             windowloading,
+            batchcheck,
             loaderror,
-            optionpage,
             searchpage,
+            overview,
             saveerror,
             rebuild,
+            sourcewindow,
+            bookmarks,
             welcome,
             nameentry,
             searchpageentry,
@@ -461,7 +644,26 @@ impl SimpleComponent for AppModel {
         let optlistbox = model.options.widget();
         let buttonsbox = model.posbtn.widget();
         let homebtn = gtk::Button::new();
+        let tabview = &model.tabview;
         let widgets = view_output!();
+        connecttabview(&model.tabview, &sender);
+        {
+            // Middle click opens an option in a new tab
+            let sender = sender.clone();
+            let optlistbox = optlistbox.clone();
+            let gesture = gtk::GestureClick::new();
+            gesture.set_button(2);
+            gesture.connect_pressed(move |_, _, _, y| {
+                if let Some(row) = optlistbox.row_at_y(y as i32) {
+                    if let Ok(l) = row.downcast::<adw::PreferencesRow>() {
+                        let text = l.title().to_string();
+                        let v = optionpath::split(&text);
+                        sender.input(AppMsg::OpenOptionRow(v, true));
+                    }
+                }
+            });
+            optlistbox.add_controller(&gesture);
+        }
 
         {
             let group = RelmActionGroup::<MenuActionGroup>::new();
@@ -470,6 +672,16 @@ impl SimpleComponent for AppModel {
                 prefsender.input(AppMsg::ShowPrefMenu);
             });
 
+            let overviewsender = sender.clone();
+            let overviewaction: RelmAction<OverviewAction> = RelmAction::new_stateless(move |_| {
+                overviewsender.input(AppMsg::ShowOverview);
+            });
+
+            let checksender = sender.clone();
+            let checkaction: RelmAction<CheckConfigAction> = RelmAction::new_stateless(move |_| {
+                checksender.input(AppMsg::CheckConfig);
+            });
+
             let aboutsender = sender.clone();
             let aboutaction: RelmAction<AboutAction> = RelmAction::new_stateless(move |_| {
                 aboutsender.input(AppMsg::ShowAboutPage);
@@ -483,6 +695,8 @@ impl SimpleComponent for AppModel {
                     modifiedsender.input(AppMsg::SetModifiedOnly(modified));
                 });
             group.add_action(&prefaction);
+            group.add_action(&overviewaction);
+            group.add_action(&checkaction);
             group.add_action(&aboutaction);
             group.add_action(&modifiedaction);
             let actions = group.into_action_group();
@@ -490,17 +704,20 @@ impl SimpleComponent for AppModel {
                 .main_window
                 .insert_action_group("menu", Some(&actions));
         }
+        widgets
+            .main_window
+            .insert_action_group("window", Some(&windowactions(&sender, &model.tabview)));
         {
+            // Mouse back and forward buttons
             let sender = sender.clone();
-            let group = RelmActionGroup::<WindowActionGroup>::new();
-            let searchaction: RelmAction<SearchAction> = RelmAction::new_stateless(move |_| {
-                sender.input(AppMsg::ToggleSearch);
+            let gesture = gtk::GestureClick::new();
+            gesture.set_button(0);
+            gesture.connect_pressed(move |gesture, _, _, _| match gesture.current_button() {
+                8 => sender.input(AppMsg::GoBack),
+                9 => sender.input(AppMsg::GoForward),
+                _ => {}
             });
-            group.add_action(&searchaction);
-            let actions = group.into_action_group();
-            widgets
-                .main_window
-                .insert_action_group("window", Some(&actions));
+            widgets.main_window.add_controller(&gesture);
         }
         {
             let sender = sender.clone();
@@ -520,28 +737,24 @@ impl SimpleComponent for AppModel {
             }
             AppMsg::InitialLoad(x) => {
                 info!("Received AppMsg::InitialLoad");
-                self.set_data(x.data);
-                self.set_tree(x.tree);
-                self.set_conf(x.conf);
+                self.set_db(Arc::new(x.db));
+                self.set_treeposition(vec![]);
+                self.set_treerefposition(vec![]);
+                for tab in &self.tabs {
+                    tab.controller
+                        .emit(OptPageMsg::SetConfig(self.config.clone()));
+                    tab.controller.emit(OptPageMsg::SetDb(self.db.clone()));
+                }
+                self.bookmarks.emit(BookmarksMsg::SetProfile(
+                    match (&self.config.flake, &self.config.flakearg) {
+                        (Some(flake), Some(arg)) => format!("{}#{}", flake, arg),
+                        (Some(flake), None) => flake.to_string(),
+                        _ => self.config.systemconfig.clone().unwrap_or_default(),
+                    },
+                ));
                 // trace!("CONF:\n{:#?}", self.conf);
                 self.update_position(|x| x.clear());
-                let options = self
-                    .data
-                    .iter()
-                    .map(|(k, data)| {
-                        let mut v = k.split('.').map(|x| x.to_string()).collect::<Vec<_>>();
-                        let attr = v.pop().unwrap_or_default();
-                        (
-                            k.to_string(),
-                            opconfigured(&self.conf, &v, attr),
-                            data.description
-                                .as_string()
-                                .map(|x| x.to_string())
-                                .unwrap_or_default(),
-                        )
-                    })
-                    .collect::<Vec<_>>();
-                self.searchpage.emit(SearchPageMsg::LoadOptions(options));
+                self.setdocument(x.document);
                 self.set_busy(false);
                 sender.input(AppMsg::MoveTo(vec![], vec![]));
             }
@@ -555,11 +768,12 @@ impl SimpleComponent for AppModel {
                 self.set_busy(true);
                 self.windowloading.emit(WindowAsyncHandlerMsg::RunWindow(
                     self.config.systemconfig.as_ref().unwrap().to_string(),
+                    self.config.clone(),
                 ));
             }
             AppMsg::Close => {
                 info!("Received AppMsg::Close");
-                if self.editedopts.is_empty() {
+                if !self.document.isedited() {
                     relm4::main_application().quit();
                 } else {
                     self.quitdialog.emit(QuitCheckMsg::Show);
@@ -603,276 +817,73 @@ impl SimpleComponent for AppModel {
             }
             AppMsg::MoveTo(pos, newref) if !self.busy => {
                 info!("Received AppMsg::MoveTo");
-                debug!("Moving to {:?}", pos);
-                let mut p = pos.clone();
-                let mut attributes_guard = self.attributes.guard();
-                let mut options_guard = self.options.guard();
-                let mut posbtn_guard = self.posbtn.guard();
-                let mut attrvec = vec![];
-                for i in 0..attributes_guard.len() {
-                    attrvec.push(attributes_guard.get(i).unwrap().clone());
-                }
-                if let Some(x) = attrvec.iter().find(|x| x.value.eq(&pos)) {
-                    if let Some(y) = &x.replacefor {
-                        p.pop();
-                        p.push(String::from(y));
-                    }
+                self.bookmarks.emit(BookmarksMsg::Leave);
+                self.visit(HistoryEntry::Tree(pos.to_vec(), newref.to_vec()));
+                self.filltree(pos, newref);
+                self.set_page(Page::List);
+            }
+            AppMsg::OpenOption(pos, newref) if !self.busy => {
+                info!("Received AppMsg::OpenOption");
+                trace!("NEW REFPOSITON: {:?}", newref);
+                let i = match self.selectedtab() {
+                    Some(i) => i,
+                    None => self.addtab(&sender),
+                };
+                if !self.loadtab(i, pos.to_vec(), newref.to_vec()) {
+                    return;
                 }
-
-                debug!("NEW REFPOSITON: {:?}", newref);
-
-                if let Some(x) = attrloc(&self.tree, newref.to_vec()) {
-                    let mut sortedoptions = x.options.clone();
-                    sortedoptions.sort();
-                    options_guard.clear();
-                    for op in sortedoptions {
-                        let configured = if pos.eq(&newref) {
-                            opconfigured(&self.conf, &pos, op.clone())
-                        } else {
-                            opconfigured2(
-                                self.config.systemconfig.as_ref().unwrap(),
-                                &pos,
-                                &newref,
-                                op.clone(),
-                            )
-                        };
-                        let modified = opconfigured(&self.editedopts, &pos, op.clone());
-                        if self.modifiedonly && !(configured || modified) {
-                            continue;
-                        }
-                        let mut o = pos.to_vec();
-                        let mut r = newref.to_vec();
-                        o.push(op.to_string());
-                        r.push(op.to_string());
-                        options_guard.push_back(OptPos {
-                            value: o,
-                            refvalue: r,
-                            configured,
-                            modified,
-                        });
-                    }
-                    attributes_guard.clear();
-                    let mut attributes = Vec::new();
-                    let mut hasnameorstar = AddAttrOptions::None;
-                    debug!("ATTRS {:?}", x.attributes.keys());
-                    for attr in x.attributes.keys().collect::<Vec<_>>() {
-                        if attr == "<name>" {
-                            debug!("FOUND <name> ATTR");
-                            hasnameorstar = AddAttrOptions::Name;
-                            let v = getconfvals(&self.conf, &pos);
-                            for x in v {
-                                let mut p = pos.clone();
-                                let mut r = newref.clone();
-                                p.push(x.clone());
-                                r.push(String::from("<name>"));
-                                attributes.push(AttrPos {
-                                    value: p,
-                                    refvalue: r,
-                                    configured: true,
-                                    modified: opconfigured(&self.editedopts, &pos, x),
-                                    replacefor: Some(String::from("<name>")),
-                                })
-                            }
-                            let addedvals = self.nameattrs.get(&pos.join("."));
-                            if let Some(x) = addedvals {
-                                for a in x {
-                                    let mut p = pos.clone();
-                                    let mut r = newref.clone();
-                                    p.push(a.clone());
-                                    r.push(String::from("<name>"));
-                                    attributes.push(AttrPos {
-                                        value: p,
-                                        refvalue: r,
-                                        configured: false,
-                                        modified: opconfigured(
-                                            &self.editedopts,
-                                            &pos,
-                                            a.to_string(),
-                                        ),
-                                        replacefor: Some(String::from("<name>")),
-                                    })
-                                }
-                            }
-                        } else if attr == "*" {
-                            debug!("FOUND * ATTR");
-                            hasnameorstar = AddAttrOptions::Star;
-                            let v = getarrvals(self.config.systemconfig.as_ref().unwrap(), &pos);
-                            debug!("V: {:?}", v);
-                            for i in 0..v.len() {
-                                let mut p = pos.clone();
-                                let mut r = newref.clone();
-                                p.push(i.to_string());
-                                r.push(String::from("*"));
-                                attributes.push(AttrPos {
-                                    value: p,
-                                    refvalue: r,
-                                    configured: true,
-                                    modified: opconfigured(&self.editedopts, &pos, i.to_string()),
-                                    replacefor: Some(String::from("*")),
-                                })
-                            }
-                            let s = self.starattrs.get(&pos.join(".")).unwrap_or(&0);
-                            for i in v.len()..s + v.len() {
-                                let mut p = pos.clone();
-                                let mut r = newref.clone();
-                                p.push(i.to_string());
-                                r.push(String::from("*"));
-                                attributes.push(AttrPos {
-                                    value: p,
-                                    refvalue: r,
-                                    configured: false,
-                                    modified: opconfigured(&self.editedopts, &pos, i.to_string()),
-                                    replacefor: Some(String::from("*")),
-                                })
-                            }
-                        } else {
-                            let configured = if pos.eq(&newref) {
-                                opconfigured(&self.conf, &pos, attr.to_string())
-                            } else {
-                                opconfigured2(
-                                    self.config.systemconfig.as_ref().unwrap(),
-                                    &pos,
-                                    &newref,
-                                    attr.to_string(),
-                                )
-                            };
-                            let modified =
-                                opconfigured(&self.editedopts, &newref, attr.to_string());
-                            if self.modifiedonly && !(configured || modified) {
-                                continue;
-                            }
-                            let mut p = pos.to_vec();
-                            let mut r = newref.to_vec();
-                            p.push(attr.to_string());
-                            r.push(attr.to_string());
-                            attributes.push(AttrPos {
-                                value: p,
-                                refvalue: r,
-                                configured,
-                                modified,
-                                replacefor: None,
-                            });
-                        }
-                    }
-                    if !pos.is_empty() {
-                        posbtn_guard.clear();
-                        let mut pref = vec![];
-                        let mut rref = vec![];
-                        for i in 0..pos.len() {
-                            pref.push(pos[i].clone());
-                            rref.push(newref[i].clone());
-                            posbtn_guard.push_back(AttrBtn {
-                                value: pref.to_vec(),
-                                refvalue: rref.to_vec(),
-                                opt: false,
-                            });
-                        }
+                self.visit(HistoryEntry::Option(pos.to_vec(), newref.to_vec()));
+                self.showoption(pos, newref);
+            }
+            AppMsg::OpenOptionTab(pos, newref) if !self.busy => {
+                info!("Received AppMsg::OpenOptionTab");
+                self.addtab(&sender);
+                sender.input(AppMsg::OpenOption(pos, newref));
+            }
+            AppMsg::NewTab if !self.busy => {
+                info!("Received AppMsg::NewTab");
+                match self.selectedtab().map(|i| &self.tabs[i]) {
+                    Some(tab) if !tab.opt.is_empty() => {
+                        sender.input(AppMsg::OpenOptionTab(tab.opt.to_vec(), tab.refopt.to_vec()))
                     }
-
-                    let mut x = attributes.to_vec();
-                    x.sort_by(|x, y| x.value.cmp(&y.value));
-                    for attr in x {
-                        attributes_guard.push_back(attr.clone());
+                    _ => {
+                        self.addtab(&sender);
                     }
-                    debug!("Setting HNOS {:?}", hasnameorstar);
-                    self.nameorstar = hasnameorstar;
-                    self.position = pos;
-                    self.refposition = newref;
                 }
-                if self.position.is_empty() {
-                    self.header = HeaderBar::Title;
-                } else {
-                    self.header = HeaderBar::List;
+            }
+            AppMsg::CloseTab(page) => {
+                info!("Received AppMsg::CloseTab");
+                self.tabs.retain(|x| x.page != page);
+            }
+            AppMsg::CloseSelectedTab(view) => {
+                info!("Received AppMsg::CloseSelectedTab");
+                if let Some(page) = view.selected_page() {
+                    view.close_page(&page);
                 }
-
-                attributes_guard.drop();
-                options_guard.drop();
-                posbtn_guard.drop();
-                self.set_page(Page::List);
-                self.update_position(|_| ());
-                self.update_refposition(|_| ());
-                self.update_nameorstar(|_| ());
-                self.update_header(|_| ());
             }
-            AppMsg::OpenOption(pos, newref) if !self.busy => {
-                info!("Received AppMsg::OpenOption");
-                trace!("NEW REFPOSITON: {:?}", newref);
-                let d = match self.data.get(&newref.join(".")) {
-                    Some(x) => x,
+            AppMsg::SelectTab => {
+                info!("Received AppMsg::SelectTab");
+                self.set_optionopen(self.tabview.n_pages() > 0);
+                match self.selectedtab().map(|i| &self.tabs[i]) {
+                    Some(tab) => {
+                        if !tab.opt.is_empty() && tab.opt != self.position && !self.search {
+                            let (opt, refopt) = (tab.opt.to_vec(), tab.refopt.to_vec());
+                            self.showoption(opt, refopt);
+                        }
+                    }
                     None => {
-                        error!("No data for {:?}", newref);
-                        return;
+                        // Go back to the list the closed option was in
+                        if self.page == Page::Option {
+                            sender.input(AppMsg::MoveTo(
+                                self.position[..self.position.len().saturating_sub(1)].to_vec(),
+                                self.refposition[..self.refposition.len().saturating_sub(1)]
+                                    .to_vec(),
+                            ));
+                        }
                     }
-                };
-
-                let conf = if let Some(x) = self.editedopts.get(&pos.join(".")) {
-                    trace!("EDITED");
-                    x.to_string()
-                } else if let Some(n) = self.conf.get(&pos.join(".")) {
-                    trace!("CONFIGURED");
-                    n.to_string()
-                } else if let Ok(v) = readval(
-                    self.config.systemconfig.as_ref().unwrap(),
-                    &pos.join("."),
-                    &newref.join("."),
-                ) {
-                    trace!("READ");
-                    v
-                } else {
-                    trace!("EMPTY");
-                    String::default()
-                };
-
-                self.optionpage.emit(OptPageMsg::UpdateOption(
-                    Box::new(d.clone()),
-                    pos.to_vec(),
-                    newref.to_vec(),
-                    conf,
-                    self.data
-                        .keys()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>(),
-                ));
-
-                let mut attributes_guard = self.attributes.guard();
-                let mut options_guard = self.options.guard();
-                let mut posbtn_guard = self.posbtn.guard();
-
-                options_guard.clear();
-                attributes_guard.clear();
-                posbtn_guard.clear();
-                let mut pref = vec![];
-                let mut rref = vec![];
-                let mut p2 = pos.clone();
-                let mut r2 = newref.clone();
-                p2.pop();
-                r2.pop();
-                for i in 0..p2.len() {
-                    pref.push(p2[i].clone());
-                    rref.push(r2[i].clone());
-                    posbtn_guard.push_back(AttrBtn {
-                        value: pref.to_vec(),
-                        refvalue: rref.to_vec(),
-                        opt: false,
-                    });
                 }
-                posbtn_guard.push_back(AttrBtn {
-                    value: pos.to_vec(),
-                    refvalue: newref.to_vec(),
-                    opt: true,
-                });
-
-                attributes_guard.drop();
-                options_guard.drop();
-                posbtn_guard.drop();
-
-                self.set_position(pos);
-                self.set_refposition(newref);
-                self.set_header(HeaderBar::List);
-                self.set_page(Page::Option);
-                self.set_search(false);
             }
-            AppMsg::OpenOptionRow(pos) => {
+            AppMsg::OpenOptionRow(pos, newtab) => {
                 info!("Received AppMsg::OpenOptionRow");
                 let options_guard = self.options.guard();
                 let mut optvec = vec![];
@@ -882,13 +893,48 @@ impl SimpleComponent for AppModel {
 
                 match optvec.iter().find(|x| x.value == pos) {
                     Some(x) => {
-                        sender.input(AppMsg::OpenOption(x.value.to_vec(), x.refvalue.to_vec()));
+                        sender.input(if newtab {
+                            AppMsg::OpenOptionTab(x.value.to_vec(), x.refvalue.to_vec())
+                        } else {
+                            AppMsg::OpenOption(x.value.to_vec(), x.refvalue.to_vec())
+                        });
                     }
                     None => {
                         error!("Received AppMsg::OpenOptionRow, but no options found");
                     }
                 }
             }
+            AppMsg::ShowOverview if !self.busy => {
+                info!("Received AppMsg::ShowOverview");
+                self.visit(HistoryEntry::Overview);
+                self.bookmarks.emit(BookmarksMsg::Leave);
+                self.set_page(Page::Overview);
+                self.set_header(HeaderBar::Title);
+                self.set_search(false);
+                self.refreshoverview();
+            }
+            AppMsg::OpenConfigured(opt) => {
+                info!("Received AppMsg::OpenConfigured");
+                let pos = optionpath::split(&opt);
+                match self.db.refpath(&pos) {
+                    Some(refpos) => sender.input(AppMsg::OpenOption(pos, refpos)),
+                    None => error!("No declared option for {}", opt),
+                }
+            }
+            AppMsg::ResetOpts(opts) => {
+                info!("Received AppMsg::ResetOpts");
+                if let Err(e) = self.document.reset(&opts) {
+                    error!("Failed to reset options: {}", e);
+                }
+                for i in 0..self.tabs.len() {
+                    if opts.contains(&optionpath::join(&self.tabs[i].opt)) {
+                        let (opt, refopt) =
+                            (self.tabs[i].opt.to_vec(), self.tabs[i].refopt.to_vec());
+                        self.loadtab(i, opt, refopt);
+                    }
+                }
+                self.refreshoverview();
+            }
             AppMsg::ShowSearch if !self.busy => {
                 info!("Received AppMsg::ShowSearch");
                 self.set_header(HeaderBar::Search)
@@ -912,10 +958,17 @@ impl SimpleComponent for AppModel {
             }
             AppMsg::ShowSearchPage(s) if !self.busy => {
                 info!("Received AppMsg::ShowSearchPage");
+                self.visit(HistoryEntry::Search(s.to_string()));
                 self.searchpage
-                    .emit(SearchPageMsg::Search(s, self.editedopts.clone()));
+                    .emit(SearchPageMsg::Search(s, self.document.edits().clone()));
                 self.set_search(true)
             }
+            AppMsg::SetSearchText(s) => {
+                info!("Received AppMsg::SetSearchText");
+                self.set_searchtext(s);
+                // Keep the search page from clearing the new text
+                self.set_search(true);
+            }
             AppMsg::HideSearchPage => {
                 info!("Received AppMsg::HideSearchPage");
                 if self.page == Page::List {
@@ -931,12 +984,12 @@ impl SimpleComponent for AppModel {
                     conf: &HashMap<String, String>,
                     nameattrs: &HashMap<String, Vec<String>>,
                     starattrs: &HashMap<String, usize>,
-                    configpath: &str,
+                    document: &ConfigDocument,
                 ) -> Vec<Vec<String>> {
                     for i in 0..pos.len() {
                         if pos[i] == "<name>" {
                             let mut possiblevals = getconfvals(conf, &pos[..i]);
-                            if let Some(x) = nameattrs.get(&pos[..i].join(".")) {
+                            if let Some(x) = nameattrs.get(&optionpath::join(&pos[..i])) {
                                 possiblevals.append(&mut x.to_vec());
                             }
                             let mut out = vec![];
@@ -944,14 +997,14 @@ impl SimpleComponent for AppModel {
                                 let mut newpos = pos.clone();
                                 newpos[i] = x.clone();
                                 out.append(&mut getposdata(
-                                    &newpos, conf, nameattrs, starattrs, configpath,
+                                    &newpos, conf, nameattrs, starattrs, document,
                                 ));
                             }
                             return out;
                         } else if pos[i] == "*" {
-                            let v = getarrvals(configpath, &pos[..i]);
+                            let v = document.getarrvals(&pos[..i]);
                             let mut n = v.len();
-                            if let Some(x) = starattrs.get(&pos[..i].join(".")) {
+                            if let Some(x) = starattrs.get(&optionpath::join(&pos[..i])) {
                                 n += *x;
                             }
                             let mut out = vec![];
@@ -959,7 +1012,7 @@ impl SimpleComponent for AppModel {
                                 let mut newpos = pos.clone();
                                 newpos[i] = j.to_string();
                                 out.append(&mut getposdata(
-                                    &newpos, conf, nameattrs, starattrs, configpath,
+                                    &newpos, conf, nameattrs, starattrs, document,
                                 ));
                             }
                             return out;
@@ -970,13 +1023,13 @@ impl SimpleComponent for AppModel {
 
                 let data = getposdata(
                     &pos,
-                    &self.conf,
+                    self.document.values(),
                     &self.nameattrs,
                     &self.starattrs,
-                    self.config.systemconfig.as_ref().unwrap(),
+                    &self.document,
                 )
                 .iter()
-                .map(|x| x.join("."))
+                .map(|x| optionpath::join(x))
                 .collect::<Vec<String>>();
                 self.searchpageentry.emit(SearchEntryMsg::Show(pos, data));
             }
@@ -984,33 +1037,122 @@ impl SimpleComponent for AppModel {
                 info!("Received AppMsg::SetBusy");
                 self.set_busy(b)
             }
-            AppMsg::SaveError(msg) => {
+            AppMsg::SaveError(id, msg) => {
                 info!("Received AppMsg::SaveError");
+                self.errortab = Some(id);
                 self.saveerror.emit(SaveErrorMsg::Show(msg))
             }
             AppMsg::SaveWithError => {
                 info!("Received AppMsg::SaveWithError");
-                self.optionpage
-                    .emit(OptPageMsg::DoneSaving(true, "true\n".to_string()))
+                if let Some(tab) = self
+                    .errortab
+                    .and_then(|id| self.tabs.iter().find(|x| x.id == id))
+                {
+                    tab.controller
+                        .emit(OptPageMsg::DoneSaving(true, "true\n".to_string()))
+                }
             }
             AppMsg::SaveErrorReset => {
                 info!("Received AppMsg::SaveErrorReset");
-                self.optionpage.emit(OptPageMsg::ResetConf)
+                if let Some(tab) = self
+                    .errortab
+                    .and_then(|id| self.tabs.iter().find(|x| x.id == id))
+                {
+                    tab.controller.emit(OptPageMsg::ResetConf)
+                }
             }
             AppMsg::EditOpt(opt, value) => {
                 info!("Received AppMsg::EditOpt");
-                if self.conf.get(&opt).is_none() && value.is_empty() {
-                    self.editedopts.remove(&opt);
-                } else {
-                    self.editedopts.insert(opt, value);
+                if let Err(e) = self.document.edit(&opt, &value) {
+                    self.editfailed(&opt, e);
+                    return;
+                }
+                for tab in &self.tabs {
+                    tab.controller
+                        .emit(OptPageMsg::Edited(opt.to_string(), value.to_string()));
+                }
+                self.refreshoverview();
+            }
+            AppMsg::ShowSource(path, line) => {
+                info!("Received AppMsg::ShowSource");
+                self.sourcewindow.emit(SourceWindowMsg::Show(path, line));
+            }
+            AppMsg::EditNote(opt, note) => {
+                info!("Received AppMsg::EditNote");
+                if let Err(e) = self.document.editnote(&opt, &note) {
+                    self.editfailed(&opt, e);
+                    return;
                 }
+                for tab in &self.tabs {
+                    tab.controller
+                        .emit(OptPageMsg::EditedNote(opt.to_string(), note.to_string()));
+                }
+                self.refreshoverview();
             }
             AppMsg::Rebuild => {
                 info!("Received AppMsg::Rebuild");
-                let conf = match config::editconfigpath(
-                    self.config.systemconfig.as_ref().unwrap(),
-                    self.editedopts.clone(),
-                ) {
+                // Type check every edit up front instead of failing late in the build
+                let entries = self
+                    .document
+                    .edits()
+                    .iter()
+                    .filter(|(_, value)| !value.is_empty())
+                    .filter_map(|(opt, value)| {
+                        let pos = optionpath::split(opt);
+                        let refopt = optionpath::join(&self.db.refpath(&pos)?);
+                        Some(CheckEntry {
+                            opt: opt.to_string(),
+                            op_type: self.db.op_type(&refopt)?.to_string(),
+                            refopt,
+                            value: value.to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                if entries.is_empty() {
+                    sender.input(AppMsg::StartRebuild);
+                } else {
+                    self.set_busy(true);
+                    self.batchcheck.emit(BatchCheckHandlerMsg::Check(
+                        entries,
+                        self.db.clone(),
+                        self.config.clone(),
+                    ));
+                }
+            }
+            AppMsg::CheckConfig => {
+                info!("Received AppMsg::CheckConfig");
+                let conf = match self.document.serialize() {
+                    Ok(x) => x,
+                    Err(e) => {
+                        self.rebuild.emit(RebuildMsg::FinishError(Some(format!(
+                            "Error modifying configuration file.\n{}",
+                            e
+                        ))));
+                        return;
+                    }
+                };
+                self.rebuild
+                    .emit(RebuildMsg::Check(conf, self.config.clone()));
+            }
+            AppMsg::DoneBatchCheck(mut failed) => {
+                info!("Received AppMsg::DoneBatchCheck");
+                self.set_busy(false);
+                if failed.is_empty() {
+                    sender.input(AppMsg::StartRebuild);
+                } else {
+                    failed.sort();
+                    self.rebuild.emit(RebuildMsg::CheckFailed(
+                        failed
+                            .iter()
+                            .map(|(opt, e)| format!("{}:\n{}", opt, e))
+                            .collect::<Vec<_>>()
+                            .join("\n\n"),
+                    ));
+                }
+            }
+            AppMsg::StartRebuild => {
+                info!("Received AppMsg::StartRebuild");
+                let conf = match self.document.serialize() {
                     Ok(x) => x,
                     Err(e) => {
                         self.rebuild.emit(RebuildMsg::FinishError(Some(format!(
@@ -1034,34 +1176,41 @@ impl SimpleComponent for AppModel {
             }
             AppMsg::ResetConfig => {
                 info!("Received AppMsg::ResetConfig");
-                self.update_editedopts(|x| x.clear());
-                if self.page == Page::Option {
-                    sender.input(AppMsg::OpenOption(
-                        self.position.clone(),
-                        self.refposition.clone(),
-                    ));
+                if let Err(e) = self.document.resetall() {
+                    error!("Failed to reset options: {}", e);
+                }
+                for i in 0..self.tabs.len() {
+                    if !self.tabs[i].opt.is_empty() {
+                        let (opt, refopt) =
+                            (self.tabs[i].opt.to_vec(), self.tabs[i].refopt.to_vec());
+                        self.loadtab(i, opt, refopt);
+                    }
                 }
+                self.refreshoverview();
             }
             AppMsg::SaveConfig => {
                 info!("Received AppMsg::SaveConfig");
-                self.update_editedopts(|x| x.clear());
-                let conf = match parseconfig(self.config.systemconfig.as_ref().unwrap()) {
-                    Ok(x) => x,
-                    Err(_) => {
-                        sender.input(AppMsg::LoadError(
-                            String::from("Error loading configuration file"),
-                            format!(
-                                "<tt>{}</tt> may be an invalid configuration file",
-                                self.config.systemconfig.as_ref().unwrap()
-                            ),
-                        ));
-                        return;
-                    }
-                };
-                self.set_conf(conf);
-                sender.input(AppMsg::SetBusy(true));
-                self.set_page(Page::Loading);
-                sender.input(AppMsg::TryLoad);
+                if let Err(e) = self.document.resetall() {
+                    error!("Failed to reset options: {}", e);
+                }
+                // Only the configuration changed, the options stay loaded
+                self.set_busy(true);
+                self.windowloading.emit(WindowAsyncHandlerMsg::ReloadConfig(
+                    self.config.systemconfig.as_ref().unwrap().to_string(),
+                ));
+            }
+            AppMsg::ConfigReloaded(document) => {
+                info!("Received AppMsg::ConfigReloaded");
+                self.setdocument(document);
+                let (pos, refpos) = (self.position.to_vec(), self.refposition.to_vec());
+                self.filltree(self.treeposition.clone(), self.treerefposition.clone());
+                // Filling the tree points the header at it, so point it back at the open option
+                if self.page == Page::Option {
+                    let search = self.search;
+                    self.showoption(pos, refpos);
+                    self.set_search(search);
+                }
+                self.set_busy(false);
             }
             AppMsg::ShowPrefMenu => {
                 info!("Received AppMsg::ShowPrefMenu");
@@ -1076,11 +1225,15 @@ impl SimpleComponent for AppModel {
             AppMsg::SetDarkMode(dark) => {
                 info!("Received AppMsg::SetDarkMode");
                 let scheme = if dark { "Adwaita-dark" } else { "Adwaita" };
-                self.optionpage
-                    .emit(OptPageMsg::SetScheme(scheme.to_string()));
+                for tab in &self.tabs {
+                    tab.controller
+                        .emit(OptPageMsg::SetScheme(scheme.to_string()));
+                }
                 self.saveerror
                     .emit(SaveErrorMsg::SetScheme(scheme.to_string()));
                 self.rebuild.emit(RebuildMsg::SetScheme(scheme.to_string()));
+                self.sourcewindow
+                    .emit(SourceWindowMsg::SetScheme(scheme.to_string()));
                 self.set_scheme(sourceview5::StyleSchemeManager::default().scheme(scheme));
             }
             AppMsg::AddAttr => {
@@ -1094,14 +1247,14 @@ impl SimpleComponent for AppModel {
 
                 match self.nameorstar {
                     AddAttrOptions::Name => self.nameentry.emit(NameEntryMsg::Show(
-                        self.position.join("."),
+                        optionpath::join(&self.position),
                         attrvec
                             .iter()
                             .map(|x| x.value.last().unwrap_or(&String::default()).to_string())
                             .collect::<Vec<_>>(),
                     )),
                     AddAttrOptions::Star => {
-                        let pos = self.position.join(".");
+                        let pos = optionpath::join(&self.position);
                         self.update_starattrs(|x| {
                             x.insert(pos.to_string(), *x.get(&pos).unwrap_or(&0) + 1);
                         });
@@ -1117,7 +1270,7 @@ impl SimpleComponent for AppModel {
                 let pos = if let Some(x) = position {
                     x
                 } else {
-                    self.position.join(".")
+                    optionpath::join(&self.position)
                 };
                 self.update_nameattrs(|x| {
                     if let Some(v) = x.get(&pos) {
@@ -1147,10 +1300,7 @@ impl SimpleComponent for AppModel {
             }
             AppMsg::SaveQuit => {
                 info!("Received AppMsg::SaveQuit");
-                let conf = match config::editconfigpath(
-                    self.config.systemconfig.as_ref().unwrap(),
-                    self.editedopts.clone(),
-                ) {
+                let conf = match self.document.serialize() {
                     Ok(x) => x,
                     Err(e) => {
                         self.rebuild.emit(RebuildMsg::FinishError(Some(format!(
@@ -1164,7 +1314,9 @@ impl SimpleComponent for AppModel {
                     conf,
                     self.config.systemconfig.as_ref().unwrap().to_string(),
                 ));
-                self.editedopts.clear();
+                if let Err(e) = self.document.resetall() {
+                    error!("Failed to reset options: {}", e);
+                }
             }
             AppMsg::ShowAboutPage => {
                 let about = AboutPageModel::builder()
@@ -1181,15 +1333,585 @@ impl SimpleComponent for AppModel {
                     sender.input(AppMsg::MoveToSelf)
                 }
             }
+            AppMsg::SetFolded(folded) => {
+                info!("Received AppMsg::SetFolded");
+                self.set_folded(folded);
+            }
+            AppMsg::GoBack if !self.busy => {
+                info!("Received AppMsg::GoBack");
+                if let Some(entry) = self.back.last().cloned() {
+                    self.update_back(|x| {
+                        x.pop();
+                    });
+                    if let Some(current) = self.current.take() {
+                        self.update_forward(|x| x.push(current));
+                    }
+                    self.navigate(entry, &sender);
+                }
+            }
+            AppMsg::GoForward if !self.busy => {
+                info!("Received AppMsg::GoForward");
+                if let Some(entry) = self.forward.last().cloned() {
+                    self.update_forward(|x| {
+                        x.pop();
+                    });
+                    if let Some(current) = self.current.take() {
+                        self.update_back(|x| x.push(current));
+                    }
+                    self.navigate(entry, &sender);
+                }
+            }
             _ => {}
         }
     }
 }
 
+impl AppModel {
+    /// Index of the tab selected in the main window.
+    fn selectedtab(&self) -> Option<usize> {
+        let page = self.tabview.selected_page()?;
+        self.tabs.iter().position(|x| x.page == page)
+    }
+
+    /// Opens an empty option page in a new tab of the main window and returns its index.
+    fn addtab(&mut self, sender: &ComponentSender<Self>) -> usize {
+        let id = self.nexttab;
+        self.nexttab += 1;
+        let controller = OptPageModel::builder()
+            .launch(id)
+            .forward(sender.input_sender(), identity);
+        controller.emit(OptPageMsg::SetConfig(self.config.clone()));
+        controller.emit(OptPageMsg::SetDb(self.db.clone()));
+        if let Some(scheme) = &self.scheme {
+            controller.emit(OptPageMsg::SetScheme(scheme.id().to_string()));
+        }
+        let page = self.tabview.append(controller.widget());
+        page.set_title("New tab");
+        self.tabs.push(OptionTab {
+            id,
+            page: page.clone(),
+            controller,
+            opt: vec![],
+            refopt: vec![],
+        });
+        self.tabview.set_selected_page(&page);
+        self.tabs.len() - 1
+    }
+
+    /// Switches to a newly parsed configuration file, refreshing everything that shows its values.
+    fn setdocument(&mut self, document: ConfigDocument) {
+        self.set_document(document);
+        // Show the reloaded configuration in pages that stay open
+        for i in 0..self.tabs.len() {
+            if !self.tabs[i].opt.is_empty() {
+                let (opt, refopt) = (self.tabs[i].opt.to_vec(), self.tabs[i].refopt.to_vec());
+                self.loadtab(i, opt, refopt);
+            }
+        }
+        self.searchpage.emit(SearchPageMsg::LoadOptions(
+            self.db.clone(),
+            self.document.values().clone(),
+        ));
+        self.refreshoverview();
+    }
+
+    /// Shows an option in the tab at index `i`, returns false if the option is unknown.
+    fn loadtab(&mut self, i: usize, pos: Vec<String>, newref: Vec<String>) -> bool {
+        let d = match self.db.get(&optionpath::join(&newref)) {
+            Some(x) => x,
+            None => {
+                error!("No data for {:?}", newref);
+                return false;
+            }
+        };
+
+        let conf = if let Some(x) = self.document.edits().get(&optionpath::join(&pos)) {
+            trace!("EDITED");
+            x.to_string()
+        } else if let Some(n) = self.document.values().get(&optionpath::join(&pos)) {
+            trace!("CONFIGURED");
+            n.to_string()
+        } else if let Some(v) = self.document.readval(&pos) {
+            trace!("READ");
+            v
+        } else {
+            trace!("EMPTY");
+            String::default()
+        };
+
+        let note = if let Some(x) = self.document.notes().get(&optionpath::join(&pos)) {
+            x.to_string()
+        } else {
+            self.document.readnote(&pos).unwrap_or_default()
+        };
+
+        let tab = &mut self.tabs[i];
+        tab.controller.emit(OptPageMsg::UpdateOption(
+            Box::new(d.clone()),
+            pos.to_vec(),
+            newref.to_vec(),
+            conf,
+            note,
+            self.document
+                .modifiers()
+                .get(&optionpath::join(&pos))
+                .cloned()
+                .unwrap_or_default(),
+        ));
+        tab.page
+            .set_title(pos.last().map(|x| x.as_str()).unwrap_or_default());
+        tab.page.set_tooltip(&optionpath::join(&pos));
+        tab.opt = pos;
+        tab.refopt = newref;
+        true
+    }
+
+    /// Points the tree, header and bookmarks at the option shown in the selected tab.
+    fn showoption(&mut self, pos: Vec<String>, newref: Vec<String>) {
+        self.bookmarks
+            .emit(BookmarksMsg::Visit(pos.to_vec(), newref.to_vec()));
+
+        // Keep the tree beside the option page showing the option's siblings
+        let parent = pos[..pos.len().saturating_sub(1)].to_vec();
+        if self.treeposition != parent {
+            let parentref = newref[..newref.len().saturating_sub(1)].to_vec();
+            self.filltree(parent, parentref);
+        }
+
+        let mut posbtn_guard = self.posbtn.guard();
+        posbtn_guard.clear();
+        let mut pref = vec![];
+        let mut rref = vec![];
+        let mut p2 = pos.clone();
+        let mut r2 = newref.clone();
+        p2.pop();
+        r2.pop();
+        for i in 0..p2.len() {
+            pref.push(p2[i].clone());
+            rref.push(r2[i].clone());
+            posbtn_guard.push_back(AttrBtn {
+                value: pref.to_vec(),
+                refvalue: rref.to_vec(),
+                opt: false,
+            });
+        }
+        posbtn_guard.push_back(AttrBtn {
+            value: pos.to_vec(),
+            refvalue: newref.to_vec(),
+            opt: true,
+        });
+
+        posbtn_guard.drop();
+
+        self.set_position(pos);
+        self.set_refposition(newref);
+        self.set_header(HeaderBar::List);
+        self.set_page(Page::Option);
+        self.set_optionopen(true);
+        self.set_search(false);
+    }
+
+    /// Reports an edit that could not be applied to the configuration file and resets the pages showing it.
+    fn editfailed(&self, opt: &str, e: ConfigError) {
+        error!("Failed to apply edit to {}: {}", opt, e);
+        self.rebuild.emit(RebuildMsg::FinishError(Some(format!(
+            "Error modifying configuration file.\n{}",
+            e
+        ))));
+        for tab in &self.tabs {
+            if optionpath::join(&tab.opt) == opt {
+                tab.controller.emit(OptPageMsg::ResetConf);
+            }
+        }
+    }
+
+    /// Shows the current configuration and edits in the overview page while it is open.
+    fn refreshoverview(&self) {
+        if self.page == Page::Overview {
+            self.overview.emit(OverviewMsg::Update(
+                self.document.values().clone(),
+                self.document.edits().clone(),
+                self.document.notes().clone(),
+            ));
+        }
+    }
+
+    /// Lists the attributes and options below `pos` in the tree.
+    fn filltree(&mut self, pos: Vec<String>, newref: Vec<String>) {
+        debug!("Moving to {:?}", pos);
+        let mut p = pos.clone();
+        let mut attributes_guard = self.attributes.guard();
+        let mut options_guard = self.options.guard();
+        let mut posbtn_guard = self.posbtn.guard();
+        let mut attrvec = vec![];
+        for i in 0..attributes_guard.len() {
+            attrvec.push(attributes_guard.get(i).unwrap().clone());
+        }
+        if let Some(x) = attrvec.iter().find(|x| x.value.eq(&pos)) {
+            if let Some(y) = &x.replacefor {
+                p.pop();
+                p.push(String::from(y));
+            }
+        }
+
+        debug!("NEW REFPOSITON: {:?}", newref);
+
+        if let Some(x) = self.db.attrloc(newref.to_vec()) {
+            let mut sortedoptions = x.options.clone();
+            sortedoptions.sort();
+            options_guard.clear();
+            for op in sortedoptions {
+                let configured = if pos.eq(&newref) {
+                    opconfigured(self.document.values(), &pos, op.clone())
+                } else {
+                    self.document.configured(&pos, op.clone())
+                };
+                let modified = opconfigured(self.document.edits(), &pos, op.clone());
+                if self.modifiedonly && !(configured || modified) {
+                    continue;
+                }
+                let mut o = pos.to_vec();
+                let mut r = newref.to_vec();
+                o.push(op.to_string());
+                r.push(op.to_string());
+                let opt = optionpath::join(&o);
+                let source = self.db.source(&optionpath::join(&r)).map(|x| x.to_string());
+                let null = match self.document.edits().get(&opt) {
+                    Some(x) => x.trim() == "null",
+                    None if configured => self
+                        .document
+                        .values()
+                        .get(&opt)
+                        .cloned()
+                        .or_else(|| self.document.readval(&o))
+                        .map(|x| x.trim() == "null")
+                        .unwrap_or(false),
+                    None => false,
+                };
+                options_guard.push_back(OptPos {
+                    value: o,
+                    refvalue: r,
+                    configured,
+                    modified,
+                    null,
+                    source,
+                });
+            }
+            attributes_guard.clear();
+            let mut attributes = Vec::new();
+            let mut hasnameorstar = AddAttrOptions::None;
+            debug!("ATTRS {:?}", x.attributes.keys());
+            for attr in x.attributes.keys().collect::<Vec<_>>() {
+                if attr == "<name>" {
+                    debug!("FOUND <name> ATTR");
+                    hasnameorstar = AddAttrOptions::Name;
+                    let v = getconfvals(self.document.values(), &pos);
+                    for x in v {
+                        let mut p = pos.clone();
+                        let mut r = newref.clone();
+                        p.push(x.clone());
+                        r.push(String::from("<name>"));
+                        attributes.push(AttrPos {
+                            value: p,
+                            refvalue: r,
+                            configured: true,
+                            modified: opconfigured(self.document.edits(), &pos, x),
+                            replacefor: Some(String::from("<name>")),
+                            source: None,
+                        })
+                    }
+                    let addedvals = self.nameattrs.get(&optionpath::join(&pos));
+                    if let Some(x) = addedvals {
+                        for a in x {
+                            let mut p = pos.clone();
+                            let mut r = newref.clone();
+                            p.push(a.clone());
+                            r.push(String::from("<name>"));
+                            attributes.push(AttrPos {
+                                value: p,
+                                refvalue: r,
+                                configured: false,
+                                modified: opconfigured(self.document.edits(), &pos, a.to_string()),
+                                replacefor: Some(String::from("<name>")),
+                                source: None,
+                            })
+                        }
+                    }
+                } else if attr == "*" {
+                    debug!("FOUND * ATTR");
+                    hasnameorstar = AddAttrOptions::Star;
+                    let v = self.document.getarrvals(&pos);
+                    debug!("V: {:?}", v);
+                    for i in 0..v.len() {
+                        let mut p = pos.clone();
+                        let mut r = newref.clone();
+                        p.push(i.to_string());
+                        r.push(String::from("*"));
+                        attributes.push(AttrPos {
+                            value: p,
+                            refvalue: r,
+                            configured: true,
+                            modified: opconfigured(self.document.edits(), &pos, i.to_string()),
+                            replacefor: Some(String::from("*")),
+                            source: None,
+                        })
+                    }
+                    let s = self.starattrs.get(&optionpath::join(&pos)).unwrap_or(&0);
+                    for i in v.len()..s + v.len() {
+                        let mut p = pos.clone();
+                        let mut r = newref.clone();
+                        p.push(i.to_string());
+                        r.push(String::from("*"));
+                        attributes.push(AttrPos {
+                            value: p,
+                            refvalue: r,
+                            configured: false,
+                            modified: opconfigured(self.document.edits(), &pos, i.to_string()),
+                            replacefor: Some(String::from("*")),
+                            source: None,
+                        })
+                    }
+                } else {
+                    let configured = if pos.eq(&newref) {
+                        opconfigured(self.document.values(), &pos, attr.to_string())
+                    } else {
+                        self.document.configured(&pos, attr.to_string())
+                    };
+                    let modified = opconfigured(self.document.edits(), &newref, attr.to_string());
+                    if self.modifiedonly && !(configured || modified) {
+                        continue;
+                    }
+                    let mut p = pos.to_vec();
+                    let mut r = newref.to_vec();
+                    p.push(attr.to_string());
+                    r.push(attr.to_string());
+                    let source = self
+                        .db
+                        .namespacesource(&optionpath::join(&r))
+                        .map(|x| x.to_string());
+                    attributes.push(AttrPos {
+                        value: p,
+                        refvalue: r,
+                        configured,
+                        modified,
+                        replacefor: None,
+                        source,
+                    });
+                }
+            }
+            // Options taking a NixOS configuration, such as `containers.<name>.config`,
+            // can be browsed like the top level
+            for op in &x.options {
+                let mut r = newref.to_vec();
+                r.push(op.to_string());
+                if !isnested(innerpath(&r)) {
+                    continue;
+                }
+                let configured = if pos.eq(&newref) {
+                    opconfigured(self.document.values(), &pos, op.to_string())
+                } else {
+                    self.document.configured(&pos, op.to_string())
+                };
+                let modified = opconfigured(self.document.edits(), &pos, op.to_string());
+                if self.modifiedonly && !(configured || modified) {
+                    continue;
+                }
+                let mut p = pos.to_vec();
+                p.push(op.to_string());
+                attributes.push(AttrPos {
+                    value: p,
+                    refvalue: r,
+                    configured,
+                    modified,
+                    replacefor: None,
+                    source: None,
+                });
+            }
+            if !pos.is_empty() {
+                posbtn_guard.clear();
+                let mut pref = vec![];
+                let mut rref = vec![];
+                for i in 0..pos.len() {
+                    pref.push(pos[i].clone());
+                    rref.push(newref[i].clone());
+                    posbtn_guard.push_back(AttrBtn {
+                        value: pref.to_vec(),
+                        refvalue: rref.to_vec(),
+                        opt: false,
+                    });
+                }
+            }
+
+            let mut x = attributes.to_vec();
+            x.sort_by(|x, y| x.value.cmp(&y.value));
+            for attr in x {
+                attributes_guard.push_back(attr.clone());
+            }
+            debug!("Setting HNOS {:?}", hasnameorstar);
+            self.nameorstar = hasnameorstar;
+            self.position = pos;
+            self.refposition = newref;
+        }
+        if self.position.is_empty() {
+            self.header = HeaderBar::Title;
+        } else {
+            self.header = HeaderBar::List;
+        }
+
+        attributes_guard.drop();
+        options_guard.drop();
+        posbtn_guard.drop();
+        self.update_position(|_| ());
+        self.update_refposition(|_| ());
+        self.update_nameorstar(|_| ());
+        self.update_header(|_| ());
+        self.set_treeposition(self.position.to_vec());
+        self.set_treerefposition(self.refposition.to_vec());
+    }
+
+    /// Records a place in the navigation history.
+    fn visit(&mut self, entry: HistoryEntry) {
+        match &self.current {
+            Some(x) if *x == entry => {}
+            // Every keystroke is a new query, only keep the last one
+            Some(HistoryEntry::Search(_)) if matches!(entry, HistoryEntry::Search(_)) => {
+                self.current = Some(entry);
+            }
+            _ => {
+                if let Some(current) = self.current.take() {
+                    self.update_back(|x| x.push(current));
+                }
+                self.update_forward(|x| x.clear());
+                self.current = Some(entry);
+            }
+        }
+    }
+
+    /// Returns to a place from the navigation history without recording it again.
+    fn navigate(&mut self, entry: HistoryEntry, sender: &ComponentSender<Self>) {
+        self.current = Some(entry.clone());
+        match entry {
+            HistoryEntry::Tree(pos, refpos) => {
+                if self.search {
+                    sender.input(AppMsg::HideSearch);
+                }
+                sender.input(AppMsg::MoveTo(pos, refpos));
+            }
+            HistoryEntry::Option(pos, refpos) => {
+                if self.search {
+                    sender.input(AppMsg::HideSearch);
+                }
+                sender.input(AppMsg::OpenOption(pos, refpos));
+            }
+            HistoryEntry::Search(query) => {
+                sender.input(AppMsg::ShowSearch);
+                sender.input(AppMsg::SetSearchText(query));
+            }
+            HistoryEntry::Overview => {
+                if self.search {
+                    sender.input(AppMsg::HideSearch);
+                }
+                sender.input(AppMsg::ShowOverview);
+            }
+        }
+    }
+}
+
+/// Lets tabs of `view` be closed and dragged out into their own windows.
+fn connecttabview(view: &adw::TabView, sender: &ComponentSender<AppModel>) {
+    let closesender = sender.clone();
+    view.connect_close_page(move |_, page| {
+        closesender.input(AppMsg::CloseTab(page.clone()));
+        // Let the default handler close the page
+        false
+    });
+    let windowsender = sender.clone();
+    view.connect_create_window(move |_| Some(tabwindow(&windowsender)));
+}
+
+/// Search, navigation and tab actions of a window showing the option tabs of `view`.
+fn windowactions(
+    sender: &ComponentSender<AppModel>,
+    view: &adw::TabView,
+) -> gio::SimpleActionGroup {
+    let group = RelmActionGroup::<WindowActionGroup>::new();
+    let searchsender = sender.clone();
+    let searchaction: RelmAction<SearchAction> = RelmAction::new_stateless(move |_| {
+        searchsender.input(AppMsg::ToggleSearch);
+    });
+    let backsender = sender.clone();
+    let backaction: RelmAction<BackAction> = RelmAction::new_stateless(move |_| {
+        backsender.input(AppMsg::GoBack);
+    });
+    let forwardsender = sender.clone();
+    let forwardaction: RelmAction<ForwardAction> = RelmAction::new_stateless(move |_| {
+        forwardsender.input(AppMsg::GoForward);
+    });
+    let newtabsender = sender.clone();
+    let newtabaction: RelmAction<NewTabAction> = RelmAction::new_stateless(move |_| {
+        newtabsender.input(AppMsg::NewTab);
+    });
+    let closetabsender = sender.clone();
+    let closeview = view.clone();
+    let closetabaction: RelmAction<CloseTabAction> = RelmAction::new_stateless(move |_| {
+        closetabsender.input(AppMsg::CloseSelectedTab(closeview.clone()));
+    });
+    group.add_action(&searchaction);
+    group.add_action(&backaction);
+    group.add_action(&forwardaction);
+    group.add_action(&newtabaction);
+    group.add_action(&closetabaction);
+    group.into_action_group()
+}
+
+/// Opens a window for option tabs detached from the main window.
+fn tabwindow(sender: &ComponentSender<AppModel>) -> adw::TabView {
+    let view = adw::TabView::new();
+    view.set_vexpand(true);
+    let bar = adw::TabBar::new();
+    bar.set_view(Some(&view));
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    content.append(&adw::HeaderBar::new());
+    content.append(&bar);
+    content.append(&view);
+    let window = adw::Window::builder()
+        .application(&relm4::main_application())
+        .title("Configuration Editor")
+        .default_width(600)
+        .default_height(650)
+        .content(&content)
+        .build();
+    connecttabview(&view, sender);
+    window.insert_action_group("window", Some(&windowactions(sender, &view)));
+    // Close the window along with its last tab
+    let emptywindow = window.clone();
+    view.connect_n_pages_notify(move |x| {
+        if x.n_pages() == 0 && emptywindow.is_visible() {
+            emptywindow.close();
+        }
+    });
+    let closeview = view.clone();
+    window.connect_close_request(move |x| {
+        x.hide();
+        for i in (0..closeview.n_pages()).rev() {
+            closeview.close_page(&closeview.nth_page(i));
+        }
+        gtk::Inhibit(false)
+    });
+    window.show();
+    view
+}
+
 relm4::new_action_group!(MenuActionGroup, "menu");
 relm4::new_stateless_action!(PreferencesAction, MenuActionGroup, "preferences");
 relm4::new_stateless_action!(AboutAction, MenuActionGroup, "about");
+relm4::new_stateless_action!(OverviewAction, MenuActionGroup, "overview");
+relm4::new_stateless_action!(CheckConfigAction, MenuActionGroup, "checkconfig");
 relm4::new_stateful_action!(ModifiedAction, MenuActionGroup, "modified", u8, u8);
 
 relm4::new_action_group!(WindowActionGroup, "window");
 relm4::new_stateless_action!(SearchAction, WindowActionGroup, "search");
+relm4::new_stateless_action!(BackAction, WindowActionGroup, "back");
+relm4::new_stateless_action!(ForwardAction, WindowActionGroup, "forward");
+relm4::new_stateless_action!(NewTabAction, WindowActionGroup, "newtab");
+relm4::new_stateless_action!(CloseTabAction, WindowActionGroup, "closetab");
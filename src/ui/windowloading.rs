@@ -1,18 +1,33 @@
+use super::savechecking::nixoscontext;
 use super::window::{AppMsg, LoadValues};
-use crate::parse::config::parseconfig;
-use crate::parse::options::read;
-use crate::parse::preferences::editconfig;
+use crate::parse::document::ConfigDocument;
+use crate::parse::evaluator;
+use crate::parse::optiondb::OptionDb;
+use crate::parse::preferences::{editconfig, extraoptions};
+use crate::parse::syntax::ConfigError;
+use crate::parse::tempdir::TempDir;
 use log::*;
 use nix_data::config::configfile::NixDataConfig;
 use relm4::adw::prelude::*;
 use relm4::*;
-use std::path::Path;
+use std::{fs, path::Path, thread};
+
+// Options of `sys` in the format of the options.json nixpkgs builds for its manual
+const OPTIONSEXPR: &str = r#"let
+  lib = sys.config._module.args.pkgs.lib;
+  options = builtins.filter (x: x.visible != false && !x.internal) (lib.optionAttrSetToDocList sys.options);
+in
+builtins.listToAttrs (map (x: {
+  inherit (x) name;
+  value = builtins.removeAttrs x [ "name" "loc" "visible" "internal" "related" ];
+}) options)"#;
 
 pub struct WindowAsyncHandler;
 
 #[derive(Debug)]
 pub enum WindowAsyncHandlerMsg {
-    RunWindow(String),
+    RunWindow(String, NixDataConfig),
+    ReloadConfig(String),
     GetConfigPath(Option<NixDataConfig>),
     SetConfig(NixDataConfig),
 }
@@ -28,45 +43,71 @@ impl Worker for WindowAsyncHandler {
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
-            WindowAsyncHandlerMsg::RunWindow(path) => {
-                let optionfile = match nix_data::cache::nixos::nixosoptions() {
-                    Ok(x) => x,
-                    Err(e) => {
-                        error!("{}", e);
-                        let _ = sender.output(AppMsg::LoadError(
-                            String::from("Could not load cache"),
-                            String::from(
-                                "Try connecting to the internet or launching the application again",
-                            ),
-                        ));
-                        return;
+            WindowAsyncHandlerMsg::RunWindow(path, config) => {
+                let extra = extraoptions();
+                // Once the evaluator is running, list the options of the nixpkgs the
+                // system is built from instead of the cached ones
+                let evaluated = nixoscontext(&config).and_then(|x| evaluatedoptions(&x, &extra));
+                let db = if let Some(db) = evaluated {
+                    db
+                } else {
+                    let optionfile = match nix_data::cache::nixos::nixosoptions() {
+                        Ok(x) => x,
+                        Err(e) => {
+                            error!("{}", e);
+                            let _ = sender.output(AppMsg::LoadError(
+                                String::from("Could not load cache"),
+                                String::from(
+                                    "Try connecting to the internet or launching the application again",
+                                ),
+                            ));
+                            return;
+                        }
+                    };
+                    match OptionDb::load(&optionfile, &extra) {
+                        Ok(x) => x,
+                        Err(e) => {
+                            error!("{}", e);
+                            let _ = sender.output(AppMsg::LoadError(
+                                String::from("Could not load options"),
+                                String::from("Try launching the application again"),
+                            ));
+                            return;
+                        }
                     }
                 };
 
-                let (data, tree) = match read(&optionfile) {
+                let document = match ConfigDocument::open(&path) {
                     Ok(x) => x,
                     Err(e) => {
                         error!("{}", e);
                         let _ = sender.output(AppMsg::LoadError(
-                            String::from("Could not load options"),
-                            String::from("Try launching the application again"),
+                            String::from("Error loading configuration file"),
+                            configerrormsg(&path, &e),
                         ));
                         return;
                     }
                 };
-
-                let conf = match parseconfig(&path) {
-                    Ok(x) => x,
+                // Have the evaluator ready by the time the first option is saved,
+                // finding a flake's nixpkgs may need a download so it is not waited for
+                thread::spawn(move || {
+                    if let Some(context) = nixoscontext(&config) {
+                        evaluator::warmup(&context);
+                    }
+                });
+                let _ = sender.output(AppMsg::InitialLoad(LoadValues { db, document }));
+            }
+            WindowAsyncHandlerMsg::ReloadConfig(path) => {
+                let _ = match ConfigDocument::open(&path) {
+                    Ok(document) => sender.output(AppMsg::ConfigReloaded(document)),
                     Err(e) => {
                         error!("{}", e);
-                        let _ = sender.output(AppMsg::LoadError(
+                        sender.output(AppMsg::LoadError(
                             String::from("Error loading configuration file"),
-                            format!("<tt>{}</tt> may be an invalid configuration file", path),
-                        ));
-                        return;
+                            configerrormsg(&path, &e),
+                        ))
                     }
                 };
-                let _ = sender.output(AppMsg::InitialLoad(LoadValues { data, tree, conf }));
             }
             WindowAsyncHandlerMsg::GetConfigPath(cfg) => {
                 warn!("CFG: {:?}", cfg);
@@ -103,6 +144,46 @@ impl Worker for WindowAsyncHandler {
     }
 }
 
+/// Lists the NixOS options through the shared evaluator, `None` while it is not running.
+fn evaluatedoptions(context: &str, extra: &[String]) -> Option<OptionDb> {
+    let json = match evaluator::eval(context, OPTIONSEXPR) {
+        Ok(x) => x,
+        Err(e) => {
+            debug!("Not listing options through the evaluator: {:?}", e);
+            return None;
+        }
+    };
+    let dir = TempDir::new("nce-options").ok()?;
+    let file = dir.path().join("options.json");
+    if let Err(e) = fs::write(&file, json) {
+        warn!("Failed to write evaluated options: {}", e);
+        return None;
+    }
+    match OptionDb::load(&file.to_string_lossy(), extra) {
+        Ok(x) => Some(x),
+        Err(e) => {
+            warn!("Failed to load evaluated options: {}", e);
+            None
+        }
+    }
+}
+
+/// Explains why a configuration file could not be loaded, pointing at the
+/// offending code for syntax errors.
+pub fn configerrormsg(path: &str, e: &ConfigError) -> String {
+    match e {
+        ConfigError::Syntax(x) => format!(
+            "<tt>{}</tt> has a syntax error on line {}, column {}: {}\n\n<tt>{}</tt>",
+            gtk::glib::markup_escape_text(path),
+            x.line,
+            x.column,
+            gtk::glib::markup_escape_text(&x.message),
+            gtk::glib::markup_escape_text(&x.snippet),
+        ),
+        _ => format!("<tt>{}</tt> may be an invalid configuration file", path),
+    }
+}
+
 pub struct LoadErrorModel {
     hidden: bool,
     msg: String,